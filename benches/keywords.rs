@@ -0,0 +1,52 @@
+#![feature(test)]
+
+extern crate test;
+
+use interpreterbook::token::{keyword, Lexer, Token, KEYWORDS};
+use test::Bencher;
+
+const WORDS: &str = "let fn if else return true false null x y add
+accumulator runningtotal somethingelse anotherlongname";
+
+fn repeat(snippet: &str, times: usize) -> String {
+    return vec![snippet; times].join("\n");
+}
+
+fn words() -> Vec<&'static str> {
+    return WORDS.split_whitespace().collect::<Vec<&str>>().repeat(1000);
+}
+
+// the lookup the lexer did before the length prefilter
+fn unfiltered(ident: &str) -> Option<Token> {
+    return KEYWORDS.get(ident).cloned();
+}
+
+#[bench]
+fn bench_lookup_unfiltered(b: &mut Bencher) {
+    let words = words();
+    b.iter(|| words.iter().filter_map(|word| unfiltered(word)).count());
+}
+
+#[bench]
+fn bench_lookup_prefiltered(b: &mut Bencher) {
+    let words = words();
+    b.iter(|| words.iter().filter_map(|word| keyword(word)).count());
+}
+
+#[bench]
+fn bench_keywords(b: &mut Bencher) {
+    let input = repeat("let fn if else return true false null;", 1000);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_short_identifiers(b: &mut Bencher) {
+    let input = repeat("let add = fn(x, y) { x + y; };", 1000);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_long_identifiers(b: &mut Bencher) {
+    let input = repeat("accumulator + runningtotal * somethingelse - anotherlongname;", 1000);
+    b.iter(|| Lexer::tokens(&input));
+}
@@ -0,0 +1,52 @@
+#![feature(test)]
+
+extern crate test;
+
+use interpreterbook::token::Lexer;
+use test::Bencher;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::Generator;
+
+const KEYWORDS: &[&str] = &["let", "fn", "if", "else", "return", "true", "false", "null"];
+
+const IDENTIFIERS: &[&str] = &["foo", "bar", "counter", "accumulator", "x", "value", "items"];
+
+fn words(generator: &mut Generator, pool: &[&str], count: usize) -> String {
+    return (0..count)
+        .map(|_| generator.pick(pool))
+        .collect::<Vec<&str>>()
+        .join(" ");
+}
+
+#[bench]
+fn bench_small(b: &mut Bencher) {
+    let input = Generator::new().program(10);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_medium(b: &mut Bencher) {
+    let input = Generator::new().program(1_000);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_large(b: &mut Bencher) {
+    let input = Generator::new().program(100_000);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_many_keywords(b: &mut Bencher) {
+    let input = words(&mut Generator::new(), KEYWORDS, 10_000);
+    b.iter(|| Lexer::tokens(&input));
+}
+
+#[bench]
+fn bench_many_identifiers(b: &mut Bencher) {
+    let input = words(&mut Generator::new(), IDENTIFIERS, 10_000);
+    b.iter(|| Lexer::tokens(&input));
+}
@@ -0,0 +1,10 @@
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use interpreterbook::token::{Lexer, Token};
+
+pub fn tokens(input: &str) -> Vec<Token> {
+    return Lexer::new(input).collect();
+}
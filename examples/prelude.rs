@@ -0,0 +1,15 @@
+use interpreterbook::prelude::*;
+
+fn main() {
+    let tokens = Lexer::tokens("let add = fn(x, y) { x + y; };");
+    println!("{} tokens", tokens.len());
+
+    for token in Lexer::new("let five = 5;") {
+        if let Token::Identifier(name) = token {
+            println!("identifier {}", name);
+        }
+    }
+
+    let repl = Repl::new();
+    println!("{:?}", repl.line("5 == 5"));
+}
@@ -1,22 +1,35 @@
-use std::io::BufRead;
+use std::io::{BufRead, Read};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use interpreterbook::repl::Repl;
 
-const PROMPT: &'static str = ">>";
-
 fn main() -> Result<()> {
-    let repl = Repl::new();
+    let mut repl = Repl::new();
+    let args = std::env::args().collect::<Vec<String>>();
+
+    if let Some(idx) = args.iter().position(|arg| arg == "--prompt") {
+        let prompt = args
+            .get(idx + 1)
+            .ok_or_else(|| anyhow!("--prompt needs a value"))?;
+        repl.set_prompt(prompt);
+    }
 
     let stdin = std::io::stdin();
 
+    if args.iter().any(|arg| arg == "--dump-tokens") {
+        let mut input = String::new();
+        stdin.lock().read_to_string(&mut input)?;
+        return repl.dump_tokens(&input);
+    }
+
     loop {
-        println!("{}", PROMPT);
-        if let Some(Ok(ref line)) = stdin.lock().lines().next() {
-            for item in repl.line(line).iter() {
-                println!("{:?}", item);
-            }
+        repl.prompt()?;
+        match stdin.lock().lines().next() {
+            Some(line) => repl.run_line(&line?)?,
+            None => return Ok(()),
+        }
+        if repl.exited() {
+            return Ok(());
         }
     }
 }
-
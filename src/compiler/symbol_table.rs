@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SymbolScope {
+    Global,
+    Local,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    num_definitions: usize,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        return SymbolTable::default();
+    }
+
+    // a function body's scope, anything not defined in it resolves through outer
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        return SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        };
+    }
+
+    // hands the outer table back when leaving a function body
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        return self.outer.map(|outer| *outer);
+    }
+
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = match self.outer {
+            Some(_) => SymbolScope::Local,
+            None => SymbolScope::Global,
+        };
+
+        let symbol = Symbol {
+            name: String::from(name),
+            scope,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        self.store.insert(String::from(name), symbol.clone());
+
+        return symbol;
+    }
+
+    pub fn resolve(&self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        return self.outer.as_ref()?.resolve(name);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Symbol, SymbolScope, SymbolTable};
+    use pretty_assertions::assert_eq;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        return Symbol {
+            name: String::from(name),
+            scope,
+            index,
+        };
+    }
+
+    #[test]
+    fn test_define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+
+        assert_eq!(global.resolve("a"), Some(symbol("a", SymbolScope::Global, 0)));
+        assert_eq!(global.resolve("b"), Some(symbol("b", SymbolScope::Global, 1)));
+        assert_eq!(global.resolve("c"), None);
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 1));
+        assert_eq!(global.resolve("a"), Some(symbol("a", SymbolScope::Global, 1)));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("a"), symbol("a", SymbolScope::Local, 0));
+        assert_eq!(local.resolve("a"), Some(symbol("a", SymbolScope::Local, 0)));
+
+        let global = local.into_outer().expect("local has an outer table");
+        assert_eq!(global.resolve("a"), Some(symbol("a", SymbolScope::Global, 1)));
+    }
+
+    #[test]
+    fn test_nested_scopes() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+        global.define("b");
+
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("c");
+        first.define("d");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define("e");
+        second.define("f");
+
+        assert_eq!(second.resolve("a"), Some(symbol("a", SymbolScope::Global, 0)));
+        assert_eq!(second.resolve("b"), Some(symbol("b", SymbolScope::Global, 1)));
+        assert_eq!(second.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+        assert_eq!(second.resolve("d"), Some(symbol("d", SymbolScope::Local, 1)));
+        assert_eq!(second.resolve("e"), Some(symbol("e", SymbolScope::Local, 0)));
+        assert_eq!(second.resolve("f"), Some(symbol("f", SymbolScope::Local, 1)));
+        assert_eq!(second.resolve("g"), None);
+
+        let first = second.into_outer().expect("second has an outer table");
+        assert_eq!(first.resolve("e"), None);
+        assert_eq!(first.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+    }
+}
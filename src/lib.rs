@@ -1,5 +1,11 @@
 #![feature(once_cell)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
 pub mod token;
+#[cfg(feature = "std")]
 pub mod repl;
-
+pub mod prelude;
+#[cfg(feature = "std")]
+pub mod compiler;
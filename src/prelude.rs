@@ -0,0 +1,3 @@
+#[cfg(feature = "std")]
+pub use crate::repl::Repl;
+pub use crate::token::{LexError, Lexer, Token, TokenKind};
@@ -1,15 +1,70 @@
-use crate::token::{Token, Lexer};
+use std::io::{IsTerminal, Write};
+use std::time::Instant;
 
-pub struct Repl { }
+use anyhow::Result;
+
+use crate::token::{keywords, tokens_to_json, Token, Lexer};
+
+const COMMANDS: &[(&str, &str)] = &[
+    (":help", "list the repl commands and language keywords"),
+    (":tokens", "lex the rest of the line and print the tokens as a table"),
+    ("exit", "leave the repl"),
+];
+
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const MAGENTA: &str = "35";
+
+const PROMPT: &str = ">> ";
+
+// what :bench lexes, repeated so each run has a few thousand tokens
+const BENCH_INPUT: &str = "let add = fn(x, y) { return x + y; };
+let result = add(5, 10) * 2 - 3 / 4;
+if (result != 10) { `got ${result}` } else { \"ten\" }
+";
+const BENCH_REPEAT: usize = 100;
+
+pub struct Repl {
+    out: Box<dyn Write>,
+    colorize: bool,
+    keep_comments: bool,
+    prompt: String,
+    exited: bool,
+}
 
 impl Repl {
     pub fn new() -> Repl {
+        let mut repl = Repl::with_writer(std::io::stdout());
+        repl.colorize(std::io::stdout().is_terminal());
+        return repl;
+    }
+
+    pub fn with_writer(out: impl Write + 'static) -> Repl {
         return Repl {
+            out: Box::new(out),
+            colorize: false,
+            keep_comments: false,
+            prompt: String::from(PROMPT),
+            exited: false,
         };
     }
 
+    // only has an effect when built with the colors feature
+    pub fn colorize(&mut self, colorize: bool) {
+        self.colorize = colorize && cfg!(feature = "colors");
+    }
+
+    pub fn keep_comments(&mut self, keep: bool) {
+        self.keep_comments = keep;
+    }
+
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = String::from(prompt);
+    }
+
     pub fn line(&self, line: &str) -> Vec<Token> {
-        let lex = Lexer::new(line);
+        let lex = Lexer::new(line).keep_comments(self.keep_comments);
         let mut out = vec![];
 
         for token in lex.into_iter() {
@@ -19,4 +74,328 @@ impl Repl {
         return out;
     }
 
+    pub fn prompt(&mut self) -> Result<()> {
+        write!(self.out, "{}", self.prompt)?;
+        self.out.flush()?;
+
+        return Ok(());
+    }
+
+    // set once an `exit` line has been run, the caller should stop reading
+    pub fn exited(&self) -> bool {
+        return self.exited;
+    }
+
+    pub fn run_line(&mut self, line: &str) -> Result<()> {
+        if line.trim() == ":help" {
+            return self.help();
+        }
+
+        if line.trim() == "exit" {
+            self.exited = true;
+            return Ok(());
+        }
+
+        // not listed in :help, it is only for poking at performance by hand
+        if let Some(rest) = line.trim_start().strip_prefix(":bench") {
+            return self.bench(rest.trim());
+        }
+
+        if let Some(rest) = line.trim_start().strip_prefix(":tokens") {
+            write!(self.out, "{}", Repl::format_tokens(&self.line(rest)))?;
+            self.out.flush()?;
+            return Ok(());
+        }
+
+        for item in self.line(line).iter() {
+            let text = self.display(item);
+            writeln!(self.out, "{}", text)?;
+        }
+        self.out.flush()?;
+
+        return Ok(());
+    }
+
+    pub fn dump_tokens(&mut self, input: &str) -> Result<()> {
+        writeln!(self.out, "{}", tokens_to_json(&Lexer::tokens(input)))?;
+        self.out.flush()?;
+
+        return Ok(());
+    }
+
+    // one row per token: index, kind, then the payload for tokens that carry one
+    pub fn format_tokens(tokens: &[Token]) -> String {
+        let index_width = tokens.len().saturating_sub(1).to_string().len();
+        let kind_width = tokens
+            .iter()
+            .map(|token| format!("{:?}", token.kind()).len())
+            .max()
+            .unwrap_or(0);
+
+        let mut out = String::new();
+        for (idx, token) in tokens.iter().enumerate() {
+            let payload = match token {
+                Token::Illegal(_)
+                | Token::Identifier(_)
+                | Token::Int(_)
+                | Token::RawInt(_)
+                | Token::TypedInt { .. }
+                | Token::String(_)
+                | Token::TemplateString(_)
+                | Token::Comment(_) => token.to_string(),
+                _ => String::new(),
+            };
+            let row = format!(
+                "{:>index_width$}  {:<kind_width$}  {}",
+                idx,
+                format!("{:?}", token.kind()),
+                payload,
+            );
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+
+        return out;
+    }
+
+    fn display(&self, token: &Token) -> String {
+        let text = format!("{:?}", token);
+        if !self.colorize {
+            return text;
+        }
+
+        let color = if let Token::Illegal(_) = token {
+            RED
+        } else if token.is_keyword() {
+            MAGENTA
+        } else if token.is_literal() {
+            GREEN
+        } else if token.is_operator() {
+            YELLOW
+        } else {
+            return text;
+        };
+
+        return format!("\x1b[{}m{}\x1b[0m", color, text);
+    }
+
+    fn bench(&mut self, runs: &str) -> Result<()> {
+        let runs = match runs.parse::<usize>() {
+            Ok(runs) => runs,
+            Err(_) => {
+                writeln!(self.out, "usage: :bench <n>")?;
+                self.out.flush()?;
+                return Ok(());
+            }
+        };
+
+        let input = BENCH_INPUT.repeat(BENCH_REPEAT);
+        let start = Instant::now();
+        let mut tokens = 0;
+        for _ in 0..runs {
+            tokens += Lexer::new(&input).count();
+        }
+
+        writeln!(self.out, "lexed {} tokens in {} runs: {:?}", tokens, runs, start.elapsed())?;
+        self.out.flush()?;
+
+        return Ok(());
+    }
+
+    fn help(&mut self) -> Result<()> {
+        writeln!(self.out, "commands:")?;
+        for (name, description) in COMMANDS {
+            writeln!(self.out, "  {:<8} {}", name, description)?;
+        }
+
+        writeln!(self.out, "keywords:")?;
+        writeln!(self.out, "  {}", keywords().join(" "))?;
+        self.out.flush()?;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use super::Repl;
+    use crate::token::{keywords, Lexer, Token};
+    use pretty_assertions::assert_eq;
+
+    // the integration tests keep their own copy in tests/common
+    #[derive(Clone, Default)]
+    struct Captured(Rc<RefCell<Vec<u8>>>);
+
+    impl Captured {
+        fn text(&self) -> String {
+            return String::from_utf8_lossy(&self.0.borrow()).into_owned();
+        }
+    }
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn test_repl_writer() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+
+        repl.prompt()?;
+        repl.run_line("let x = 5;")?;
+        assert_eq!(captured.text(), ">> Let
+Identifier(\"x\")
+Assign
+Int(5)
+Semicolon
+");
+
+        repl.prompt()?;
+        repl.run_line("!= ==")?;
+        assert_eq!(captured.text(), ">> Let
+Identifier(\"x\")
+Assign
+Int(5)
+Semicolon
+>> NotEqual
+Equal
+");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_repl_custom_prompt() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+        repl.set_prompt("monkey> ");
+
+        repl.prompt()?;
+        repl.run_line("5")?;
+        repl.prompt()?;
+        assert_eq!(captured.text(), "monkey> Int(5)\nmonkey> ");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_repl_keep_comments() {
+        let mut repl = Repl::with_writer(Captured::default());
+        assert_eq!(repl.line("x // why"), vec![Token::Identifier(Box::from("x"))]);
+
+        repl.keep_comments(true);
+        assert_eq!(
+            repl.line("x // why"),
+            vec![Token::Identifier(Box::from("x")), Token::Comment(Box::from(" why"))]
+        );
+    }
+
+    #[test]
+    fn test_repl_help() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+
+        repl.run_line(":help")?;
+        let text = captured.text();
+
+        for name in [":help", ":tokens", "exit"] {
+            assert!(text.contains(name), "missing command {}", name);
+        }
+        for keyword in keywords() {
+            assert!(text.contains(keyword), "missing keyword {}", keyword);
+        }
+        assert!(!text.contains("Identifier"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_format_tokens() -> anyhow::Result<()> {
+        let tokens = Lexer::tokens("let name = \"hi\"; 1 2 3 4 5 6 7");
+        assert_eq!(Repl::format_tokens(&tokens[..5]), "0  Let
+1  Identifier  name
+2  Assign
+3  String      \"hi\"
+4  Semicolon
+");
+        assert_eq!(Repl::format_tokens(&tokens).lines().nth(10), Some("10  Int         6"));
+        assert_eq!(Repl::format_tokens(&[]), "");
+
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+        repl.run_line(":tokens x;")?;
+        assert_eq!(captured.text(), "0  Identifier  x\n1  Semicolon\n");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_repl_bench() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+
+        repl.run_line(":bench 3")?;
+        let text = captured.text();
+        assert!(text.starts_with("lexed "), "{}", text);
+        assert!(text.contains(" in 3 runs: "), "{}", text);
+        assert!(text.trim_end().ends_with('s'), "{}", text);
+
+        repl.run_line(":bench lots")?;
+        assert!(captured.text().ends_with("usage: :bench <n>\n"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_repl_exit() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+
+        repl.run_line("exit x")?;
+        assert!(!repl.exited());
+        repl.run_line(" exit ")?;
+        assert!(repl.exited());
+        assert_eq!(captured.text(), "Identifier(\"exit\")\nIdentifier(\"x\")\n");
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_repl_colors_off() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+        repl.colorize(false);
+
+        repl.run_line("let x = 5 + @;")?;
+        assert!(!captured.text().contains('\x1b'));
+
+        return Ok(());
+    }
+
+    #[cfg(feature = "colors")]
+    #[test]
+    fn test_repl_colors_on() -> anyhow::Result<()> {
+        let captured = Captured::default();
+        let mut repl = Repl::with_writer(captured.clone());
+        repl.colorize(true);
+
+        repl.run_line("let x @")?;
+        assert_eq!(captured.text(), "\x1b[35mLet\x1b[0m
+Identifier(\"x\")
+\x1b[31mIllegal(\"@\")\x1b[0m
+");
+
+        return Ok(());
+    }
 }
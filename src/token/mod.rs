@@ -1,18 +1,23 @@
-use std::{iter::Peekable, str::Chars};
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, vec, vec::Vec};
+use core::{fmt, hash::{Hash, Hasher}, iter, mem, ops::Range, str::FromStr};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Token {
     Let,
     Function,
     True,
     False,
+    Null,
     If,
     Else,
     Return,
+    Const,
     Equal,
     NotEqual,
 
-    Illegal,
+    Illegal(Box<str>),
     Assign,
     Plus,
     Comma,
@@ -21,6 +26,8 @@ pub enum Token {
     Rparen,
     Lsquirlybrace,
     Rsquirlybrace,
+    Lbracket,
+    Rbracket,
     Minus,
 
     Bang,
@@ -29,52 +36,784 @@ pub enum Token {
     Lt,
     Gt,
 
-    Identifier(String),
+    Tilde,
+    Amp,
+    Pipe,
+    Caret,
+    Shl,
+    Shr,
+
+    Dot,
+    Spread,
+    DotDot,
+    DotDotEq,
+
+    Backslash,
+    Arrow,
+
+    // only produced with Lexer::indentation
+    Indent,
+    Dedent,
+
+    // boxed rather than String/Vec so every token stays 24 bytes
+    Identifier(Box<str>),
     Int(usize),
+    // an int exactly as written, only produced with Lexer::keep_int_text
+    RawInt(Box<str>),
+    TypedInt { value: u64, suffix: IntType },
+    String(Box<str>),
+    TemplateString(Box<[TemplatePart]>),
+    Comment(Box<str>),
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum TokenKind {
+    Let,
+    Function,
+    True,
+    False,
+    Null,
+    If,
+    Else,
+    Return,
+    Const,
+    Equal,
+    NotEqual,
+
+    Illegal,
+    Assign,
+    Plus,
+    Comma,
+    Semicolon,
+    Lparen,
+    Rparen,
+    Lsquirlybrace,
+    Rsquirlybrace,
+    Lbracket,
+    Rbracket,
+    Minus,
+
+    Bang,
+    Asterisk,
+    Slash,
+    Lt,
+    Gt,
+
+    Tilde,
+    Amp,
+    Pipe,
+    Caret,
+    Shl,
+    Shr,
+
+    Dot,
+    Spread,
+    DotDot,
+    DotDotEq,
+
+    Backslash,
+    Arrow,
+
+    Indent,
+    Dedent,
+
+    Identifier,
+    Int,
+    RawInt,
+    TypedInt,
+    String,
+    TemplateString,
+    Comment,
+}
+
+// where an operator can sit relative to its operands
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+    Both,
+}
+
+// the type suffix of an integer literal like `5u8` or `7usize`
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum IntType {
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    I8,
+    I16,
+    I32,
+    I64,
+    Isize,
+}
+
+impl IntType {
+    fn from_suffix(suffix: &str) -> Option<IntType> {
+        return match suffix {
+            "u8" => Some(IntType::U8),
+            "u16" => Some(IntType::U16),
+            "u32" => Some(IntType::U32),
+            "u64" => Some(IntType::U64),
+            "usize" => Some(IntType::Usize),
+            "i8" => Some(IntType::I8),
+            "i16" => Some(IntType::I16),
+            "i32" => Some(IntType::I32),
+            "i64" => Some(IntType::I64),
+            "isize" => Some(IntType::Isize),
+            _ => None,
+        };
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        return match self {
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::Usize => "usize",
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::Isize => "isize",
+        };
+    }
+
+    // literals are never negative, so this is the largest value that fits
+    pub fn max(&self) -> u64 {
+        return match self {
+            IntType::U8 => u8::MAX as u64,
+            IntType::U16 => u16::MAX as u64,
+            IntType::U32 => u32::MAX as u64,
+            IntType::U64 => u64::MAX,
+            IntType::Usize => usize::MAX as u64,
+            IntType::I8 => i8::MAX as u64,
+            IntType::I16 => i16::MAX as u64,
+            IntType::I32 => i32::MAX as u64,
+            IntType::I64 => i64::MAX as u64,
+            IntType::Isize => isize::MAX as u64,
+        };
+    }
+}
+
+#[derive(Debug, Eq, Clone)]
+pub enum TemplatePart {
+    Literal(String),
+    // the source between `${` and `}`, span is its byte range in the input
+    Expression { source: String, span: Range<usize> },
+}
+
+// the span only says where the template was, so equal templates compare
+// equal wherever they sit in the input
+impl PartialEq for TemplatePart {
+    fn eq(&self, other: &TemplatePart) -> bool {
+        return match (self, other) {
+            (TemplatePart::Literal(a), TemplatePart::Literal(b)) => a == b,
+            (
+                TemplatePart::Expression { source: a, .. },
+                TemplatePart::Expression { source: b, .. },
+            ) => a == b,
+            _ => false,
+        };
+    }
+}
+
+impl Hash for TemplatePart {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            TemplatePart::Literal(str) => str.hash(state),
+            TemplatePart::Expression { source, .. } => source.hash(state),
+        }
+    }
+}
+
+// a byte range into the source
+pub type Span = Range<usize>;
+
+// a `//` line comment, text excludes the slashes and span covers all of it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comment {
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn kind(&self) -> TokenKind {
+        return match self {
+            Token::Let => TokenKind::Let,
+            Token::Function => TokenKind::Function,
+            Token::True => TokenKind::True,
+            Token::False => TokenKind::False,
+            Token::Null => TokenKind::Null,
+            Token::If => TokenKind::If,
+            Token::Else => TokenKind::Else,
+            Token::Return => TokenKind::Return,
+            Token::Const => TokenKind::Const,
+            Token::Equal => TokenKind::Equal,
+            Token::NotEqual => TokenKind::NotEqual,
+            Token::Illegal(_) => TokenKind::Illegal,
+            Token::Assign => TokenKind::Assign,
+            Token::Plus => TokenKind::Plus,
+            Token::Comma => TokenKind::Comma,
+            Token::Semicolon => TokenKind::Semicolon,
+            Token::Lparen => TokenKind::Lparen,
+            Token::Rparen => TokenKind::Rparen,
+            Token::Lsquirlybrace => TokenKind::Lsquirlybrace,
+            Token::Rsquirlybrace => TokenKind::Rsquirlybrace,
+            Token::Lbracket => TokenKind::Lbracket,
+            Token::Rbracket => TokenKind::Rbracket,
+            Token::Minus => TokenKind::Minus,
+            Token::Bang => TokenKind::Bang,
+            Token::Asterisk => TokenKind::Asterisk,
+            Token::Slash => TokenKind::Slash,
+            Token::Lt => TokenKind::Lt,
+            Token::Gt => TokenKind::Gt,
+            Token::Tilde => TokenKind::Tilde,
+            Token::Amp => TokenKind::Amp,
+            Token::Pipe => TokenKind::Pipe,
+            Token::Caret => TokenKind::Caret,
+            Token::Shl => TokenKind::Shl,
+            Token::Shr => TokenKind::Shr,
+            Token::Dot => TokenKind::Dot,
+            Token::Spread => TokenKind::Spread,
+            Token::DotDot => TokenKind::DotDot,
+            Token::DotDotEq => TokenKind::DotDotEq,
+            Token::Backslash => TokenKind::Backslash,
+            Token::Arrow => TokenKind::Arrow,
+            Token::Indent => TokenKind::Indent,
+            Token::Dedent => TokenKind::Dedent,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::Int(_) => TokenKind::Int,
+            Token::RawInt(_) => TokenKind::RawInt,
+            Token::TypedInt { .. } => TokenKind::TypedInt,
+            Token::String(_) => TokenKind::String,
+            Token::TemplateString(_) => TokenKind::TemplateString,
+            Token::Comment(_) => TokenKind::Comment,
+        };
+    }
+
+    // externally tagged, the same shape serde would produce by default
+    pub fn to_json(&self) -> String {
+        return match self {
+            Token::Identifier(ident) => format!("{{\"Identifier\":{}}}", json_string(ident)),
+            Token::Illegal(str) => format!("{{\"Illegal\":{}}}", json_string(str)),
+            Token::Comment(str) => format!("{{\"Comment\":{}}}", json_string(str)),
+            Token::Int(value) => format!("{{\"Int\":{}}}", value),
+            Token::RawInt(raw) => format!("{{\"RawInt\":{}}}", json_string(raw)),
+            Token::TypedInt { value, suffix } => format!(
+                "{{\"TypedInt\":{{\"value\":{},\"suffix\":\"{:?}\"}}}}",
+                value, suffix
+            ),
+            Token::String(str) => format!("{{\"String\":{}}}", json_string(str)),
+            Token::TemplateString(parts) => {
+                let parts = parts.iter().map(TemplatePart::to_json).collect::<Vec<String>>();
+                format!("{{\"TemplateString\":[{}]}}", parts.join(","))
+            }
+            token => format!("\"{:?}\"", token),
+        };
+    }
+
+    pub fn as_int(&self) -> Option<usize> {
+        return match self {
+            Token::Int(value) => Some(*value),
+            Token::RawInt(raw) => int_value(raw),
+            _ => None,
+        };
+    }
+
+    pub fn as_identifier(&self) -> Option<&str> {
+        return match self {
+            Token::Identifier(ident) => Some(ident),
+            _ => None,
+        };
+    }
+
+    pub fn is_keyword(&self) -> bool {
+        return matches!(
+            self,
+            Token::Let
+                | Token::Function
+                | Token::True
+                | Token::False
+                | Token::Null
+                | Token::If
+                | Token::Else
+                | Token::Return
+                | Token::Const
+        );
+    }
+
+    // true, false and null are both keywords and literals
+    pub fn is_literal(&self) -> bool {
+        return matches!(
+            self,
+            Token::Int(_)
+                | Token::RawInt(_)
+                | Token::TypedInt { .. }
+                | Token::String(_)
+                | Token::TemplateString(_)
+                | Token::True
+                | Token::False
+                | Token::Null
+        );
+    }
+
+    pub fn is_operator(&self) -> bool {
+        return matches!(
+            self,
+            Token::Equal
+                | Token::NotEqual
+                | Token::Assign
+                | Token::Plus
+                | Token::Minus
+                | Token::Bang
+                | Token::Asterisk
+                | Token::Slash
+                | Token::Lt
+                | Token::Gt
+                | Token::Tilde
+                | Token::Amp
+                | Token::Pipe
+                | Token::Caret
+                | Token::Shl
+                | Token::Shr
+                | Token::Dot
+                | Token::Spread
+                | Token::DotDot
+                | Token::DotDotEq
+                | Token::Backslash
+                | Token::Arrow
+        );
+    }
+
+    // scope names for editors and other external highlighters
+    pub fn highlight_group(&self) -> &'static str {
+        return match self {
+            Token::Illegal(_) => "error",
+            Token::Comment(_) => "comment",
+            Token::Identifier(_) => "identifier",
+            Token::Int(_) | Token::RawInt(_) | Token::TypedInt { .. } => "number",
+            Token::String(_) | Token::TemplateString(_) => "string",
+            Token::Indent | Token::Dedent => "whitespace",
+            token if token.is_keyword() => "keyword",
+            token if token.is_operator() => "operator",
+            _ => "punctuation",
+        };
+    }
+
+    // Both means prefix and infix, like `-` for negation and subtraction
+    pub fn fixity(&self) -> Option<Fixity> {
+        return match self {
+            Token::Minus => Some(Fixity::Both),
+            Token::Bang | Token::Tilde | Token::Spread | Token::Backslash => Some(Fixity::Prefix),
+            Token::Equal
+            | Token::NotEqual
+            | Token::Assign
+            | Token::Plus
+            | Token::Asterisk
+            | Token::Slash
+            | Token::Lt
+            | Token::Gt
+            | Token::Amp
+            | Token::Pipe
+            | Token::Caret
+            | Token::Shl
+            | Token::Shr
+            | Token::Dot
+            | Token::DotDot
+            | Token::DotDotEq
+            | Token::Arrow => Some(Fixity::Infix),
+            _ => None,
+        };
+    }
+
+    pub fn is_delimiter(&self) -> bool {
+        return matches!(
+            self,
+            Token::Comma
+                | Token::Semicolon
+                | Token::Lparen
+                | Token::Rparen
+                | Token::Lsquirlybrace
+                | Token::Rsquirlybrace
+                | Token::Lbracket
+                | Token::Rbracket
+        );
+    }
+}
+
+impl TemplatePart {
+    pub fn to_json(&self) -> String {
+        return match self {
+            TemplatePart::Literal(str) => format!("{{\"Literal\":{}}}", json_string(str)),
+            TemplatePart::Expression { source, span } => format!(
+                "{{\"Expression\":{{\"source\":{},\"span\":{{\"start\":{},\"end\":{}}}}}}}",
+                json_string(source),
+                span.start,
+                span.end
+            ),
+        };
+    }
+}
+
+// renders the source text the token lexes from, so lexing the output of
+// joined Display impls gives back the same tokens. the exceptions are
+// Indent/Dedent, which render as nothing, and Illegal, whose control chars
+// are escaped to stay printable and so lex back as different tokens
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Token::Let => write!(f, "let"),
+            Token::Function => write!(f, "fn"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::Null => write!(f, "null"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::Return => write!(f, "return"),
+            Token::Const => write!(f, "const"),
+            Token::Equal => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            // a raw control char would garble whatever terminal prints this
+            Token::Illegal(str) => write!(f, "{}", escape_control(str)),
+            Token::Assign => write!(f, "="),
+            Token::Plus => write!(f, "+"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Lparen => write!(f, "("),
+            Token::Rparen => write!(f, ")"),
+            Token::Lsquirlybrace => write!(f, "{{"),
+            Token::Rsquirlybrace => write!(f, "}}"),
+            Token::Lbracket => write!(f, "["),
+            Token::Rbracket => write!(f, "]"),
+            Token::Minus => write!(f, "-"),
+            Token::Bang => write!(f, "!"),
+            Token::Asterisk => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Lt => write!(f, "<"),
+            Token::Gt => write!(f, ">"),
+            Token::Tilde => write!(f, "~"),
+            Token::Amp => write!(f, "&"),
+            Token::Pipe => write!(f, "|"),
+            Token::Caret => write!(f, "^"),
+            Token::Shl => write!(f, "<<"),
+            Token::Shr => write!(f, ">>"),
+            Token::Dot => write!(f, "."),
+            Token::Spread => write!(f, "..."),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEq => write!(f, "..="),
+            Token::Backslash => write!(f, "\\"),
+            Token::Arrow => write!(f, "->"),
+            // nothing in the source spells these, the whitespace does
+            Token::Indent | Token::Dedent => Ok(()),
+            Token::Identifier(ident) => write!(f, "{}", ident),
+            Token::Int(value) => write!(f, "{}", value),
+            Token::RawInt(raw) => write!(f, "{}", raw),
+            Token::TypedInt { value, suffix } => write!(f, "{}{}", value, suffix.suffix()),
+            Token::String(str) => write!(f, "\"{}\"", escape(str)),
+            Token::TemplateString(parts) => {
+                write!(f, "`")?;
+                for part in parts.iter() {
+                    match part {
+                        TemplatePart::Literal(str) => write!(f, "{}", str)?,
+                        TemplatePart::Expression { source, .. } => write!(f, "${{{}}}", source)?,
+                    }
+                }
+                write!(f, "`")
+            }
+            Token::Comment(text) => write!(f, "//{}", text),
+        };
+    }
 }
 
-static KEYWORDS: phf::Map<&'static str, Token> = phf::phf_map! {
+pub static KEYWORDS: phf::Map<&'static str, Token> = phf::phf_map! {
     "true" => Token::True,
     "false" => Token::False,
+    "null" => Token::Null,
     "fn" => Token::Function,
     "let" => Token::Let,
     "if" => Token::If,
     "else" => Token::Else,
     "return" => Token::Return,
+    "const" => Token::Const,
 };
 
+// every keyword is between these lengths, anything outside can skip the map
+const KEYWORD_MIN_LEN: usize = 2;
+const KEYWORD_MAX_LEN: usize = 6;
+
+pub fn keyword(ident: &str) -> Option<Token> {
+    if ident.len() < KEYWORD_MIN_LEN || ident.len() > KEYWORD_MAX_LEN {
+        return None;
+    }
+    return KEYWORDS.get(ident).cloned();
+}
+
+pub fn keywords() -> Vec<&'static str> {
+    let mut out = KEYWORDS.keys().copied().collect::<Vec<&'static str>>();
+    out.sort();
+    return out;
+}
+
+// one token per line, for diffing against snapshot files
+pub fn tokens_to_snapshot(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        out.push_str(&format!("{:?}\n", token));
+    }
+
+    return out;
+}
+
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+    let tokens = tokens.iter().map(Token::to_json).collect::<Vec<String>>();
+    return format!("[{}]", tokens.join(","));
+}
+
+fn unescape(str: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = str.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '"' => out.push('"'),
+            '\\' => out.push('\\'),
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+
+                let mut hex = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => hex.push(c),
+                    }
+                }
+
+                if hex.is_empty() || hex.len() > 6 {
+                    return None;
+                }
+                // from_u32 rejects surrogates and anything past 10FFFF
+                let code = u32::from_str_radix(&hex, 16).ok()?;
+                out.push(char::from_u32(code)?);
+            }
+            _ => return None,
+        }
+    }
+
+    return Some(out);
+}
+
+// digits as read by Lexer::read_digits, so any `_` sits between two digits
+fn int_value<T: FromStr>(digits: &str) -> Option<T> {
+    if digits.contains('_') {
+        return digits.replace('_', "").parse::<T>().ok();
+    }
+    return digits.parse::<T>().ok();
+}
+
+fn escape_control(str: &str) -> String {
+    let mut out = String::new();
+    for c in str.chars() {
+        if c.is_control() {
+            out.push_str(&format!("\\x{:02x}", c as u32));
+        } else {
+            out.push(c);
+        }
+    }
+
+    return out;
+}
+
+// the inverse of unescape
+fn escape(str: &str) -> String {
+    let mut out = String::new();
+    for c in str.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    return out;
+}
+
+fn json_string(str: &str) -> String {
+    let mut out = String::from("\"");
+    for c in str.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    return out;
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
-    pub chars: Peekable<Chars<'a>>,
+    source: &'a str,
+    position: usize,
+    line: usize,
+    max_token_len: Option<usize>,
+    case_insensitive_keywords: bool,
+    keep_comments: bool,
+    no_keywords: bool,
+    keep_int_text: bool,
+
+    indentation: bool,
+    // widths of the open indented blocks, innermost last
+    indents: Vec<usize>,
+    indent_char: Option<char>,
+    pending_dedents: usize,
+    // the last line whose leading whitespace was looked at
+    indent_line: usize,
+
+    // why the Illegal token being lexed was produced, for Fallible
+    illegal_reason: IllegalReason,
+
+    // tokens lexed ahead by peek_n, each with where lexing it started
+    peeked: VecDeque<(Checkpoint, Lexed)>,
 }
 
-impl<'a> Iterator for Lexer<'a> {
-    type Item = Token;
+// a token with the span it was lexed from, so the adapters don't depend
+// on where the cursor is once peek_n has run ahead
+#[derive(Debug)]
+struct Lexed {
+    token: Token,
+    start: usize,
+    end: usize,
+    reason: IllegalReason,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.skip_whitespace();
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum IllegalReason {
+    Character,
+    TooLong,
+    BadSuffix,
+    IntOutOfRange,
+    Indentation,
+    UnterminatedString,
+    InvalidEscape,
+    UnterminatedTemplate,
+    UnterminatedHeredoc,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checkpoint {
+    position: usize,
+    line: usize,
+
+    indents: Vec<usize>,
+    indent_char: Option<char>,
+    pending_dedents: usize,
+    indent_line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    // next() hands out anything peek_n buffered before lexing more
+    fn lex(&mut self) -> Option<Token> {
+        self.illegal_reason = IllegalReason::Character;
+        self.skip_ignored();
+        if self.indentation {
+            if let Some(token) = self.indentation_token() {
+                return Some(token);
+            }
+        }
+        let start = self.position;
 
         loop {
             match self.read_char() {
                 Some('*') => return Some(Token::Asterisk),
                 Some('!') => {
                     if let Some(c) = self.peek() {
-                        if *c == '=' {
+                        if c == '=' {
                             self.read_char();
                             return Some(Token::NotEqual);
                         }
                     }
                     return Some(Token::Bang);
                 }
-                Some('/') => return Some(Token::Slash),
-                Some('>') => return Some(Token::Gt),
-                Some('<') => return Some(Token::Lt),
-                Some('-') => return Some(Token::Minus),
+                Some('/') => {
+                    // only reachable with keep_comments, otherwise skipped as trivia
+                    if self.peek() == Some('/') {
+                        while let Some(_) = self.next_if(|x| *x != '\n') {}
+                        let text = &self.source[start + 2..self.position];
+                        return Some(Token::Comment(Box::from(text)));
+                    }
+                    return Some(Token::Slash);
+                }
+                // only a doubled angle bracket is a shift, a single one stays a
+                // comparison. `&` and `|` are always single char tokens for now.
+                Some('>') => {
+                    if let Some(c) = self.peek() {
+                        if c == '>' {
+                            self.read_char();
+                            return Some(Token::Shr);
+                        }
+                    }
+                    return Some(Token::Gt);
+                }
+                Some('<') => {
+                    if let Some(c) = self.peek() {
+                        if c == '<' {
+                            self.read_char();
+                            // `<<<` straight into a word opens a heredoc
+                            let rest = &self.source[self.position..];
+                            if rest.starts_with('<')
+                                && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+                            {
+                                self.read_char();
+                                return match self.read_heredoc() {
+                                    Some(body) => Some(Token::String(Box::from(body))),
+                                    None => {
+                                        self.illegal_reason = IllegalReason::UnterminatedHeredoc;
+                                        Some(self.illegal(start))
+                                    }
+                                };
+                            }
+                            return Some(Token::Shl);
+                        }
+                    }
+                    return Some(Token::Lt);
+                }
+                Some('~') => return Some(Token::Tilde),
+                Some('&') => return Some(Token::Amp),
+                Some('|') => return Some(Token::Pipe),
+                Some('^') => return Some(Token::Caret),
+                Some('-') => {
+                    if self.next_if(|c| *c == '>').is_some() {
+                        return Some(Token::Arrow);
+                    }
+                    return Some(Token::Minus);
+                }
+                Some('\\') => return Some(Token::Backslash),
                 Some('+') => return Some(Token::Plus),
                 Some(',') => return Some(Token::Comma),
                 Some('=') => {
                     if let Some(c) = self.peek() {
-                        if *c == '=' {
+                        if c == '=' {
                             self.read_char();
                             return Some(Token::Equal);
                         }
@@ -86,232 +825,2290 @@ impl<'a> Iterator for Lexer<'a> {
                 Some(')') => return Some(Token::Rparen),
                 Some('{') => return Some(Token::Lsquirlybrace),
                 Some('}') => return Some(Token::Rsquirlybrace),
+                Some('[') => return Some(Token::Lbracket),
+                Some(']') => return Some(Token::Rbracket),
+                Some('"') => match self.read_string() {
+                    Some(str) => return Some(Token::String(str.into_boxed_str())),
+                    None => return Some(self.illegal(start)),
+                },
+                Some('.') => {
+                    if self.peek() != Some('.') {
+                        return Some(Token::Dot);
+                    }
+                    self.read_char();
+
+                    match self.peek() {
+                        Some('.') => {
+                            self.read_char();
+                            return Some(Token::Spread);
+                        }
+                        Some('=') => {
+                            self.read_char();
+                            return Some(Token::DotDotEq);
+                        }
+                        _ => return Some(Token::DotDot),
+                    }
+                }
+                Some('`') => match self.read_template() {
+                    Some(parts) => return Some(Token::TemplateString(parts.into_boxed_slice())),
+                    None => {
+                        self.illegal_reason = IllegalReason::UnterminatedTemplate;
+                        return Some(self.illegal(start));
+                    }
+                },
 
                 Some(c) if c.is_digit(10) => {
-                    let str = self.keep_reading(c, |c| c.is_digit(10));
-                    let str = str.into_iter().collect::<String>();
-                    return Some(Token::Int(
-                        str::parse::<usize>(&str).expect("this should always work"),
-                    ));
+                    let str = self.read_digits(c);
+                    if self.too_long(str) {
+                        return Some(self.illegal(start));
+                    }
+
+                    if let Some(c) = self.next_if(|c| c.is_ascii_alphabetic()) {
+                        let suffix = self.keep_reading(c, |c| c.is_ascii_alphanumeric());
+                        // the limit is on the whole literal, suffix included
+                        let literal = &self.source[start..self.position];
+                        if self.too_long(literal) {
+                            return Some(self.illegal(start));
+                        }
+                        let value = int_value::<u64>(str);
+                        return Some(match (IntType::from_suffix(suffix), value) {
+                            (Some(suffix), Some(value)) if value <= suffix.max() => {
+                                Token::TypedInt { value, suffix }
+                            }
+                            (None, _) => {
+                                self.illegal_reason = IllegalReason::BadSuffix;
+                                self.illegal(start)
+                            }
+                            (Some(_), _) => {
+                                self.illegal_reason = IllegalReason::IntOutOfRange;
+                                self.illegal(start)
+                            }
+                        });
+                    }
+
+                    // too big for a usize is as unrepresentable as a bad suffix
+                    return match int_value::<usize>(str) {
+                        Some(_) if self.keep_int_text => Some(Token::RawInt(Box::from(str))),
+                        Some(value) => Some(Token::Int(value)),
+                        None => {
+                            self.illegal_reason = IllegalReason::IntOutOfRange;
+                            Some(self.illegal(start))
+                        }
+                    };
                 }
 
                 Some(c) if c.is_ascii_alphabetic() => {
                     let ident = self.keep_reading(c, |c| c.is_ascii_alphabetic());
-                    let ident = ident.into_iter().collect::<String>();
+                    if self.too_long(ident) {
+                        return Some(self.illegal(start));
+                    }
 
-                    if let Some((_, v)) = KEYWORDS.get_entry(&ident) {
-                        return Some(v.clone());
+                    let keyword = if self.no_keywords {
+                        None
+                    } else if self.case_insensitive_keywords {
+                        keyword(&ident.to_ascii_lowercase())
+                    } else {
+                        keyword(ident)
+                    };
+                    if let Some(keyword) = keyword {
+                        return Some(keyword);
                     }
-                    return Some(Token::Identifier(ident));
+                    return Some(Token::Identifier(Box::from(ident)));
                 }
 
-                Some(_) => return Some(Token::Illegal),
+                // a run of illegal chars is one token, up to whitespace or
+                // anything that could start a real token
+                Some(_) => {
+                    while let Some(_) = self.next_if(|c| !c.is_whitespace() && !starts_token(c)) {}
+                    return Some(self.illegal(start));
+                }
                 _ => return None,
             }
         }
     }
 }
 
-impl<'a> Lexer<'a> {
-    pub fn new(code: &'a str) -> Lexer<'a> {
-        return Lexer {
-            chars: code.chars().peekable(),
-        };
-    }
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token;
 
-    fn peek(&mut self) -> Option<&char> {
-        return self.chars.peek();
+    fn next(&mut self) -> Option<Self::Item> {
+        return self.next_lexed().map(|lexed| lexed.token);
     }
+}
 
-    fn read_char(&mut self) -> Option<char> {
-        return self.chars.next();
-    }
+fn starts_token(c: &char) -> bool {
+    return c.is_ascii_alphanumeric() || "*!/><~&|^-+,=;(){}[]\".`\\".contains(*c);
+}
 
-    fn skip_whitespace(&mut self) {
-        while let Some(_) = self.chars.next_if(|x| x.is_whitespace()) {}
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+    // character is the first of the run, len covers all of it
+    IllegalCharacter { character: char, position: usize, len: usize },
+    TokenTooLong { position: usize, len: usize },
+    InvalidIntSuffix { position: usize, len: usize },
+    IntOutOfRange { position: usize, len: usize },
+    // mixed tabs and spaces, or a dedent to no open block's width
+    BadIndentation { position: usize, len: usize },
+    UnterminatedString { position: usize, len: usize },
+    InvalidEscape { position: usize, len: usize },
+    UnterminatedTemplate { position: usize, len: usize },
+    // also an opening `<<<END` line with more after the word
+    UnterminatedHeredoc { position: usize, len: usize },
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            LexError::IllegalCharacter { character, position, len } => write!(
+                f,
+                "illegal character {:?} at {}..{}",
+                character,
+                position,
+                position + len
+            ),
+            LexError::TokenTooLong { position, len } => write!(
+                f,
+                "token of length {} is too long at {}..{}",
+                len,
+                position,
+                position + len
+            ),
+            LexError::InvalidIntSuffix { position, len } => {
+                write!(f, "invalid integer suffix at {}..{}", position, position + len)
+            }
+            LexError::IntOutOfRange { position, len } => {
+                write!(f, "integer literal out of range at {}..{}", position, position + len)
+            }
+            LexError::BadIndentation { position, len } => {
+                write!(f, "inconsistent indentation at {}..{}", position, position + len)
+            }
+            LexError::UnterminatedString { position, len } => {
+                write!(f, "unterminated string at {}..{}", position, position + len)
+            }
+            LexError::InvalidEscape { position, len } => {
+                write!(f, "invalid escape in string at {}..{}", position, position + len)
+            }
+            LexError::UnterminatedTemplate { position, len } => {
+                write!(f, "unterminated template string at {}..{}", position, position + len)
+            }
+            LexError::UnterminatedHeredoc { position, len } => {
+                write!(f, "unterminated heredoc at {}..{}", position, position + len)
+            }
+        };
     }
+}
 
-    fn keep_reading(&mut self, c: char, f: impl Fn(&char) -> bool) -> Vec<char> {
-        let mut out = vec![c];
-        while let Some(c) = self.chars.next_if(&f) {
-            out.push(c);
-        }
+#[cfg(feature = "std")]
+impl std::error::Error for LexError {}
 
-        return out;
-    }
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseTokenError {
+    Empty,
+    Illegal(Box<str>),
+    MultipleTokens,
 }
 
-#[cfg(test)]
-mod test {
+impl fmt::Display for ParseTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ParseTokenError::Empty => write!(f, "no token in input"),
+            ParseTokenError::Illegal(str) => write!(f, "illegal token {:?}", str),
+            ParseTokenError::MultipleTokens => write!(f, "more than one token in input"),
+        };
+    }
+}
 
-    use super::{Lexer, Token};
-    use pretty_assertions::assert_eq;
+#[cfg(feature = "std")]
+impl std::error::Error for ParseTokenError {}
 
-    #[test]
-    fn test_lexer_iterator() {
-        let input = "=+(){},;";
-        let expected = vec![
-            Token::Assign,
-            Token::Plus,
-            Token::Lparen,
-            Token::Rparen,
-            Token::Lsquirlybrace,
-            Token::Rsquirlybrace,
-            Token::Comma,
-            Token::Semicolon,
-        ];
+impl FromStr for Token {
+    type Err = ParseTokenError;
 
-        let lexer = Lexer::new(input);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lexer = Lexer::new(s);
+        let token = lexer.next().ok_or(ParseTokenError::Empty)?;
+        if lexer.next().is_some() {
+            return Err(ParseTokenError::MultipleTokens);
+        }
 
-        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+        return match token {
+            Token::Illegal(str) => Err(ParseTokenError::Illegal(str)),
+            token => Ok(token),
+        };
     }
+}
 
-    #[test]
-    fn test_lexer_2() {
-        let input = "let five = 5;
+#[derive(Debug)]
+pub struct Fallible<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Iterator for Fallible<'a> {
+    type Item = Result<Token, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexed = self.lexer.next_lexed()?;
+        let text = match lexed.token {
+            Token::Illegal(text) => text,
+            token => return Some(Ok(token)),
+        };
+        let start = lexed.start;
+        let len = lexed.end - start;
+
+        return Some(Err(match lexed.reason {
+            IllegalReason::Character => LexError::IllegalCharacter {
+                character: text.chars().next().expect("illegal tokens are never empty"),
+                position: start,
+                len,
+            },
+            IllegalReason::TooLong => LexError::TokenTooLong { position: start, len },
+            IllegalReason::BadSuffix => LexError::InvalidIntSuffix { position: start, len },
+            IllegalReason::IntOutOfRange => LexError::IntOutOfRange { position: start, len },
+            // the whitespace before the token the cursor is on
+            IllegalReason::Indentation => LexError::BadIndentation {
+                position: start - text.len(),
+                len: text.len(),
+            },
+            IllegalReason::UnterminatedString => {
+                LexError::UnterminatedString { position: start, len }
+            }
+            IllegalReason::InvalidEscape => LexError::InvalidEscape { position: start, len },
+            IllegalReason::UnterminatedTemplate => {
+                LexError::UnterminatedTemplate { position: start, len }
+            }
+            IllegalReason::UnterminatedHeredoc => {
+                LexError::UnterminatedHeredoc { position: start, len }
+            }
+        }));
+    }
+}
+
+// each token with the slice of the source it was lexed from
+pub struct WithText<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Iterator for WithText<'a> {
+    type Item = (Token, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let lexed = self.lexer.next_lexed()?;
+        return Some((lexed.token, &self.lexer.source[lexed.start..lexed.end]));
+    }
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(code: &'a str) -> Lexer<'a> {
+        return Lexer {
+            source: code,
+            position: 0,
+            line: 1,
+            max_token_len: None,
+            case_insensitive_keywords: false,
+            keep_comments: false,
+            no_keywords: false,
+            keep_int_text: false,
+            indentation: false,
+            indents: vec![],
+            indent_char: None,
+            pending_dedents: 0,
+            indent_line: 0,
+            illegal_reason: IllegalReason::Character,
+            peeked: VecDeque::new(),
+        };
+    }
+
+    pub fn max_token_len(mut self, len: usize) -> Lexer<'a> {
+        self.max_token_len = Some(len);
+        return self;
+    }
+
+    pub fn case_insensitive_keywords(mut self, enabled: bool) -> Lexer<'a> {
+        self.case_insensitive_keywords = enabled;
+        return self;
+    }
+
+    pub fn keep_comments(mut self, keep: bool) -> Lexer<'a> {
+        self.keep_comments = keep;
+        return self;
+    }
+
+    // every word lexes as an identifier, keywords included
+    pub fn no_keywords(mut self, enabled: bool) -> Lexer<'a> {
+        self.no_keywords = enabled;
+        return self;
+    }
+
+    // ints lex as RawInt so `007` and `1_000` can be written back as they were.
+    // `_` digit separators are only read in this mode
+    pub fn keep_int_text(mut self, keep: bool) -> Lexer<'a> {
+        self.keep_int_text = keep;
+        return self;
+    }
+
+    // Indent/Dedent around blocks of deeper leading whitespace. blank lines
+    // don't count
+    pub fn indentation(mut self, enabled: bool) -> Lexer<'a> {
+        self.indentation = enabled;
+        return self;
+    }
+
+    // a `#!` line is only a shebang at the very start of the input
+    pub fn skip_shebang(mut self, skip: bool) -> Lexer<'a> {
+        if skip && self.position == 0 && self.source.starts_with("#!") {
+            while let Some(c) = self.read_char() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        }
+        return self;
+    }
+
+    // with tokens peeked, the checkpoint is from before the first of them
+    pub fn save(&self) -> Checkpoint {
+        if let Some((checkpoint, _)) = self.peeked.front() {
+            return checkpoint.clone();
+        }
+        return self.checkpoint();
+    }
+
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.peeked.clear();
+        self.position = checkpoint.position;
+        self.line = checkpoint.line;
+        self.indents = checkpoint.indents;
+        self.indent_char = checkpoint.indent_char;
+        self.pending_dedents = checkpoint.pending_dedents;
+        self.indent_line = checkpoint.indent_line;
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        return Checkpoint {
+            position: self.position,
+            line: self.line,
+            indents: self.indents.clone(),
+            indent_char: self.indent_char,
+            pending_dedents: self.pending_dedents,
+            indent_line: self.indent_line,
+        };
+    }
+
+    // zero based like Iterator::nth, peek_n(0) is the token next() returns
+    pub fn peek_n(&mut self, n: usize) -> Option<&Token> {
+        while self.peeked.len() <= n {
+            let checkpoint = self.checkpoint();
+            let lexed = self.lex_spanned()?;
+            self.peeked.push_back((checkpoint, lexed));
+        }
+
+        return self.peeked.get(n).map(|(_, lexed)| &lexed.token);
+    }
+
+    fn next_lexed(&mut self) -> Option<Lexed> {
+        if let Some((_, lexed)) = self.peeked.pop_front() {
+            return Some(lexed);
+        }
+        return self.lex_spanned();
+    }
+
+    // the span starts after the trivia lex() skips
+    fn lex_spanned(&mut self) -> Option<Lexed> {
+        self.skip_ignored();
+        let start = self.position;
+        let token = self.lex()?;
+
+        return Some(Lexed {
+            token,
+            start,
+            end: self.position,
+            reason: self.illegal_reason,
+        });
+    }
+
+    // peeked tokens haven't been consumed, so they don't move the line
+    pub fn line(&self) -> usize {
+        if let Some((checkpoint, _)) = self.peeked.front() {
+            return checkpoint.line;
+        }
+        return self.line;
+    }
+
+    pub fn fallible(self) -> Fallible<'a> {
+        return Fallible { lexer: self };
+    }
+
+    pub fn with_text(self) -> WithText<'a> {
+        return WithText { lexer: self };
+    }
+
+    pub fn tokens(input: &str) -> Vec<Token> {
+        return Lexer::new(input).collect();
+    }
+
+    pub fn for_each_token(input: &str, f: impl FnMut(Token)) {
+        Lexer::new(input).for_each(f);
+    }
+
+    // each char with the one after it, the same view next() gets from peek
+    pub fn char_pairs(input: &str) -> impl Iterator<Item = (char, Option<char>)> + '_ {
+        let mut chars = input.chars().peekable();
+        return iter::from_fn(move || {
+            let c = chars.next()?;
+            return Some((c, chars.peek().copied()));
+        });
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stats(input: &str) -> HashMap<TokenKind, usize> {
+        let mut out = HashMap::new();
+        Lexer::for_each_token(input, |token| *out.entry(token.kind()).or_insert(0) += 1);
+
+        return out;
+    }
+
+    // length is in source bytes, so quotes count. ties keep the first token
+    pub fn longest_token(input: &str) -> Option<(Token, usize)> {
+        let mut longest: Option<(Token, usize)> = None;
+        for (token, text) in Lexer::new(input).with_text() {
+            if longest.as_ref().is_none_or(|(_, len)| text.len() > *len) {
+                longest = Some((token, text.len()));
+            }
+        }
+
+        return longest;
+    }
+
+    // spans cover just the leading whitespace of each offending line
+    pub fn lint_indentation(input: &str) -> Vec<Span> {
+        let mut out = vec![];
+        let mut line_start = 0;
+        for line in input.split('\n') {
+            let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            let leading = &line[..indent];
+            if leading.contains(' ') && leading.contains('\t') {
+                out.push(line_start..line_start + indent);
+            }
+            line_start += line.len() + 1;
+        }
+
+        return out;
+    }
+
+    // brackets inside strings, templates and comments are part of those
+    // tokens, so they never count
+    pub fn is_balanced(input: &str) -> bool {
+        let mut open = vec![];
+        for token in Lexer::new(input) {
+            let expected = match token {
+                Token::Lparen | Token::Lsquirlybrace | Token::Lbracket => {
+                    open.push(token);
+                    continue;
+                }
+                Token::Rparen => Token::Lparen,
+                Token::Rsquirlybrace => Token::Lsquirlybrace,
+                Token::Rbracket => Token::Lbracket,
+                _ => continue,
+            };
+
+            if open.pop() != Some(expected) {
+                return false;
+            }
+        }
+
+        return open.is_empty();
+    }
+
+    // most common first, ties ordered by kind so the result is deterministic
+    #[cfg(feature = "std")]
+    pub fn frequency(input: &str) -> Vec<(TokenKind, usize)> {
+        let mut out = Lexer::stats(input).into_iter().collect::<Vec<(TokenKind, usize)>>();
+        out.sort_by(|(a_kind, a), (b_kind, b)| b.cmp(a).then(a_kind.cmp(b_kind)));
+
+        return out;
+    }
+
+    // comments after the last token have nothing to attach to and are dropped
+    pub fn tokens_with_trivia(input: &str) -> Vec<(Vec<Comment>, Token)> {
+        let mut lexer = Lexer::new(input);
+        let mut out = vec![];
+
+        loop {
+            let comments = lexer.skip_trivia();
+            match lexer.next() {
+                Some(token) => out.push((comments, token)),
+                None => return out,
+            }
+        }
+    }
+
+    // one group per source line, a token belongs to the line it starts on
+    pub fn line_tokens(input: &str) -> Vec<Vec<Token>> {
+        let mut lexer = Lexer::new(input);
+        let mut out: Vec<Vec<Token>> = vec![];
+
+        loop {
+            lexer.skip_ignored();
+            let line = lexer.line();
+            let token = match lexer.next() {
+                Some(token) => token,
+                None => break,
+            };
+
+            while out.len() < line {
+                out.push(vec![]);
+            }
+            out[line - 1].push(token);
+        }
+
+        while out.len() < input.lines().count() {
+            out.push(vec![]);
+        }
+
+        return out;
+    }
+
+    fn peek(&self) -> Option<char> {
+        return self.source[self.position..].chars().next();
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.advance(c);
+        return Some(c);
+    }
+
+    fn next_if(&mut self, f: impl Fn(&char) -> bool) -> Option<char> {
+        let c = self.peek()?;
+        if !f(&c) {
+            return None;
+        }
+        self.advance(c);
+        return Some(c);
+    }
+
+    fn advance(&mut self, c: char) {
+        self.position += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+    }
+
+    fn illegal(&self, start: usize) -> Token {
+        return Token::Illegal(Box::from(&self.source[start..self.position]));
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(_) = self.next_if(|x| x.is_whitespace()) {}
+    }
+
+    // whatever sits between tokens, comments count only when they aren't kept
+    fn skip_ignored(&mut self) {
+        if self.keep_comments {
+            self.skip_whitespace();
+        } else {
+            self.skip_trivia();
+        }
+    }
+
+    fn skip_trivia(&mut self) -> Vec<Comment> {
+        let mut comments = vec![];
+
+        loop {
+            self.skip_whitespace();
+            if !self.source[self.position..].starts_with("//") {
+                return comments;
+            }
+
+            let start = self.position;
+            while let Some(_) = self.next_if(|x| *x != '\n') {}
+            comments.push(Comment {
+                text: String::from(&self.source[start + 2..self.position]),
+                span: start..self.position,
+            });
+        }
+    }
+
+    // reads up to the closing quote and decodes escapes. an unterminated string
+    // or a bad escape is None, always consuming through the closing quote
+    fn read_string(&mut self) -> Option<String> {
+        let start = self.position;
+        self.illegal_reason = IllegalReason::UnterminatedString;
+        self.skip_string()?;
+
+        // past the closing quote only an escape can be wrong
+        self.illegal_reason = IllegalReason::InvalidEscape;
+        return unescape(&self.source[start..self.position - 1]);
+    }
+
+    // called with the cursor on the next token. mixing tabs and spaces, or
+    // dedenting to a width no block was opened at, is Illegal
+    fn indentation_token(&mut self) -> Option<Token> {
+        if self.pending_dedents > 0 {
+            self.pending_dedents -= 1;
+            return Some(Token::Dedent);
+        }
+        if self.peek().is_none() {
+            return self.indents.pop().map(|_| Token::Dedent);
+        }
+        if self.line == self.indent_line {
+            return None;
+        }
+        self.indent_line = self.line;
+
+        let line_start = self.source[..self.position].rfind('\n').map_or(0, |idx| idx + 1);
+        let indent = &self.source[line_start..self.position];
+        // the line started inside an earlier token, like a multi-line string
+        if !indent.chars().all(char::is_whitespace) {
+            return None;
+        }
+        if let Some(c) = indent.chars().next() {
+            if indent.chars().any(|x| x != c) || self.indent_char.is_some_and(|x| x != c) {
+                self.illegal_reason = IllegalReason::Indentation;
+                return Some(Token::Illegal(Box::from(indent)));
+            }
+            self.indent_char = Some(c);
+        }
+
+        let width = indent.len();
+        if width > self.indents.last().copied().unwrap_or(0) {
+            self.indents.push(width);
+            return Some(Token::Indent);
+        }
+
+        while self.indents.last().is_some_and(|top| *top > width) {
+            self.indents.pop();
+            self.pending_dedents += 1;
+        }
+        if self.indents.last().copied().unwrap_or(0) != width {
+            self.illegal_reason = IllegalReason::Indentation;
+            return Some(Token::Illegal(Box::from(indent)));
+        }
+        if self.pending_dedents > 0 {
+            self.pending_dedents -= 1;
+            return Some(Token::Dedent);
+        }
+
+        return None;
+    }
+
+    // `<<<END` must end its line. the body runs up to a line that is exactly
+    // the terminator and is taken as is, without escapes or interpolation
+    // the cursor is past the opening quote. None if it's never closed
+    fn skip_string(&mut self) -> Option<()> {
+        loop {
+            match self.read_char()? {
+                '"' => return Some(()),
+                '\\' => {
+                    self.read_char()?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_heredoc(&mut self) -> Option<&'a str> {
+        let c = self.read_char()?;
+        let terminator = self.keep_reading(c, |c| c.is_ascii_alphanumeric() || *c == '_');
+        while let Some(c) = self.next_if(|c| *c != '\n') {
+            if !c.is_whitespace() {
+                return None;
+            }
+        }
+        self.read_char()?;
+
+        let body_start = self.position;
+        loop {
+            let line_start = self.position;
+            while let Some(_) = self.next_if(|c| *c != '\n') {}
+
+            if self.source[line_start..self.position].trim_end_matches('\r') == terminator {
+                if line_start == body_start {
+                    return Some("");
+                }
+                // the line break before the terminator line isn't part of the body
+                let body = &self.source[body_start..line_start - 1];
+                return Some(body.strip_suffix('\r').unwrap_or(body));
+            }
+            self.read_char()?;
+        }
+    }
+
+    // reads up to the closing backtick, None when the template or one of its
+    // `${}` expressions is never closed
+    fn read_template(&mut self) -> Option<Vec<TemplatePart>> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+
+        loop {
+            match self.read_char()? {
+                '`' => break,
+                '$' if self.peek() == Some('{') => {
+                    self.read_char();
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(mem::take(&mut literal)));
+                    }
+
+                    let start = self.position;
+                    let mut depth = 1;
+                    while depth > 0 {
+                        match self.read_char()? {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            // braces in a string don't count
+                            '"' => self.skip_string()?,
+                            _ => {}
+                        }
+                    }
+
+                    let span = start..self.position - 1;
+                    parts.push(TemplatePart::Expression {
+                        source: String::from(&self.source[span.clone()]),
+                        span,
+                    });
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+
+        return Some(parts);
+    }
+
+    // an overlong word is dropped up to the next whitespace so the lexer
+    // resyncs on whatever comes after it
+    fn too_long(&mut self, str: &str) -> bool {
+        match self.max_token_len {
+            Some(max) if str.len() > max => {
+                while let Some(_) = self.next_if(|x| !x.is_whitespace()) {}
+                self.illegal_reason = IllegalReason::TooLong;
+                return true;
+            }
+            _ => return false,
+        }
+    }
+
+    fn keep_reading(&mut self, c: char, f: impl Fn(&char) -> bool) -> &'a str {
+        let start = self.position - c.len_utf8();
+        while let Some(_) = self.next_if(&f) {}
+
+        return &self.source[start..self.position];
+    }
+
+    // with keep_int_text, a `_` with a digit on both sides separates digits.
+    // `5_`, `1__0` and `1_u8` stop at the `_`
+    fn read_digits(&mut self, c: char) -> &'a str {
+        let start = self.position - c.len_utf8();
+        loop {
+            while let Some(_) = self.next_if(|c| c.is_digit(10)) {}
+            let rest = &self.source[self.position..];
+            if !self.keep_int_text
+                || !rest.starts_with('_')
+                || !rest[1..].starts_with(|c: char| c.is_digit(10))
+            {
+                break;
+            }
+            self.advance('_');
+        }
+
+        return &self.source[start..self.position];
+    }
+}
+
+// push-style lexing over chunked input. a token that runs up to the end of
+// what has been fed so far might continue in the next chunk, so it is held
+// back (along with any trivia before it) until more input or finish()
+#[derive(Debug, Default)]
+pub struct StreamLexer {
+    buffer: String,
+    // bytes already drained from the buffer, spans are rebased by this
+    offset: usize,
+}
+
+impl StreamLexer {
+    pub fn new() -> StreamLexer {
+        return StreamLexer::default();
+    }
+
+    pub fn feed(&mut self, chunk: &str) -> Vec<Token> {
+        self.buffer.push_str(chunk);
+        return self.drain(false);
+    }
+
+    pub fn finish(&mut self) -> Vec<Token> {
+        return self.drain(true);
+    }
+
+    fn drain(&mut self, finish: bool) -> Vec<Token> {
+        let mut lexer = Lexer::new(&self.buffer);
+        let mut out = vec![];
+        let mut consumed = 0;
+
+        while let Some(token) = lexer.next() {
+            // `<<` followed by nothing or one `<` can still open a heredoc
+            let heredoc = token == Token::Shl && "<".starts_with(&self.buffer[lexer.position..]);
+            if !finish && (lexer.position == self.buffer.len() || heredoc) {
+                break;
+            }
+            consumed = lexer.position;
+            out.push(token);
+        }
+
+        if finish {
+            consumed = self.buffer.len();
+        }
+        self.buffer.drain(..consumed);
+
+        for token in out.iter_mut() {
+            if let Token::TemplateString(parts) = token {
+                for part in parts.iter_mut() {
+                    if let TemplatePart::Expression { span, .. } = part {
+                        *span = span.start + self.offset..span.end + self.offset;
+                    }
+                }
+            }
+        }
+        self.offset += consumed;
+
+        return out;
+    }
+}
+
+// a lexed program with a cursor, for parsers that want to look ahead
+// without indexing into a Vec by hand
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnexpectedToken {
+    pub expected: TokenKind,
+    // None when the stream ran out
+    pub found: Option<Token>,
+    pub index: usize,
+}
+
+impl fmt::Display for UnexpectedToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match &self.found {
+            Some(token) => write!(
+                f,
+                "expected {:?}, found {:?} at token {}",
+                self.expected, token, self.index
+            ),
+            None => write!(f, "expected {:?}, found end of input", self.expected),
+        };
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnexpectedToken {}
+
+impl TokenStream {
+    pub fn peek(&self) -> Option<&Token> {
+        return self.tokens.get(self.cursor);
+    }
+
+    // only advances when the next token is of the expected kind
+    pub fn expect(&mut self, kind: TokenKind) -> Result<Token, UnexpectedToken> {
+        return match self.peek() {
+            Some(token) if token.kind() == kind => Ok(self.next().expect("just peeked")),
+            found => Err(UnexpectedToken {
+                expected: kind,
+                found: found.cloned(),
+                index: self.cursor,
+            }),
+        };
+    }
+
+    pub fn cursor(&self) -> usize {
+        return self.cursor;
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.tokens.len());
+    }
+
+    pub fn len(&self) -> usize {
+        return self.tokens.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.tokens.is_empty();
+    }
+}
+
+impl Iterator for TokenStream {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.cursor)?.clone();
+        self.cursor += 1;
+        return Some(token);
+    }
+}
+
+impl From<Vec<Token>> for TokenStream {
+    fn from(tokens: Vec<Token>) -> TokenStream {
+        return TokenStream { tokens, cursor: 0 };
+    }
+}
+
+impl From<&str> for TokenStream {
+    fn from(input: &str) -> TokenStream {
+        return TokenStream::from(Lexer::tokens(input));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{
+        keywords, tokens_to_json, tokens_to_snapshot, Comment, Fixity, IntType, LexError,
+        Lexer, StreamLexer, ParseTokenError, TemplatePart, Token, TokenKind, TokenStream,
+        UnexpectedToken, KEYWORD_MAX_LEN, KEYWORD_MIN_LEN,
+    };
+    use pretty_assertions::assert_eq;
+
+    const SAMPLE_ADD: &str = "let five = 5;
 let ten = 10;
 let add = fn(x, y) {
 x + y;
 };
 let result = add(five, ten);";
+
+    const SAMPLE: &str = "let five = 5;
+let ten = 10;
+let add = fn(x, y) {
+    x + y;
+};
+let result = add(five, ten);
+!-/*5;
+5 < 10 > 5;
+if (5 < 10) {
+    return true;
+} else {
+    return false;
+}
+10 == 10;
+10 != 9;";
+
+    // points at the first token that differs instead of diffing whole vecs
+    fn assert_tokens(input: &str, expected: &[Token]) {
+        let actual = Lexer::tokens(input);
+        for (idx, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+            if actual != expected {
+                panic!(
+                    "token {} of {:?} differs: expected {:?}, got {:?}",
+                    idx, input, expected, actual
+                );
+            }
+        }
+
+        if actual.len() != expected.len() {
+            panic!(
+                "{:?} lexed to {} tokens, expected {}: {:?}",
+                input,
+                actual.len(),
+                expected.len(),
+                &actual[actual.len().min(expected.len())..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_lexer_iterator() {
+        let input = "=+(){},;";
+        let expected = vec![
+            Token::Assign,
+            Token::Plus,
+            Token::Lparen,
+            Token::Rparen,
+            Token::Lsquirlybrace,
+            Token::Rsquirlybrace,
+            Token::Comma,
+            Token::Semicolon,
+        ];
+
+        let lexer = Lexer::new(input);
+
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+    }
+
+    #[test]
+    fn test_lexer_2() {
+        let expected = [
+            Token::Let,
+            Token::Identifier(Box::from("five")),
+            Token::Assign,
+            Token::Int(5),
+            Token::Semicolon,
+            Token::Let,
+            Token::Identifier(Box::from("ten")),
+            Token::Assign,
+            Token::Int(10),
+            Token::Semicolon,
+            Token::Let,
+            Token::Identifier(Box::from("add")),
+            Token::Assign,
+            Token::Function,
+            Token::Lparen,
+            Token::Identifier(Box::from("x")),
+            Token::Comma,
+            Token::Identifier(Box::from("y")),
+            Token::Rparen,
+            Token::Lsquirlybrace,
+            Token::Identifier(Box::from("x")),
+            Token::Plus,
+            Token::Identifier(Box::from("y")),
+            Token::Semicolon,
+            Token::Rsquirlybrace,
+            Token::Semicolon,
+            Token::Let,
+            Token::Identifier(Box::from("result")),
+            Token::Assign,
+            Token::Identifier(Box::from("add")),
+            Token::Lparen,
+            Token::Identifier(Box::from("five")),
+            Token::Comma,
+            Token::Identifier(Box::from("ten")),
+            Token::Rparen,
+            Token::Semicolon,
+        ];
+
+        assert_tokens(SAMPLE_ADD, &expected);
+    }
+
+    #[test]
+    fn test_lexer_3() {
+        let tokens = Lexer::tokens(SAMPLE);
+        assert_eq!(
+            tokens_to_snapshot(&tokens),
+            include_str!("snapshots/test_lexer_3.snap")
+        );
+    }
+
+    #[test]
+    fn test_lexer_bitwise() {
+        let input = "~a & b | c ^ d;
+1 << 2 >> 3;
+1 < 2 > 3;
+<<< >>>";
+        let expected = vec![
+            Token::Tilde,
+            Token::Identifier(Box::from("a")),
+            Token::Amp,
+            Token::Identifier(Box::from("b")),
+            Token::Pipe,
+            Token::Identifier(Box::from("c")),
+            Token::Caret,
+            Token::Identifier(Box::from("d")),
+            Token::Semicolon,
+            Token::Int(1),
+            Token::Shl,
+            Token::Int(2),
+            Token::Shr,
+            Token::Int(3),
+            Token::Semicolon,
+            Token::Int(1),
+            Token::Lt,
+            Token::Int(2),
+            Token::Gt,
+            Token::Int(3),
+            Token::Semicolon,
+            Token::Shl,
+            Token::Lt,
+            Token::Shr,
+            Token::Gt,
+        ];
+
+        let lexer = Lexer::new(input);
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+    }
+
+    #[test]
+    fn test_lexer_null() {
+        let input = "let x = null;
+null == null;
+nullable != null;";
+        let expected = vec![
+            Token::Let,
+            Token::Identifier(Box::from("x")),
+            Token::Assign,
+            Token::Null,
+            Token::Semicolon,
+            Token::Null,
+            Token::Equal,
+            Token::Null,
+            Token::Semicolon,
+            Token::Identifier(Box::from("nullable")),
+            Token::NotEqual,
+            Token::Null,
+            Token::Semicolon,
+        ];
+
+        let lexer = Lexer::new(input);
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+    }
+
+    #[test]
+    fn test_token_categories() {
+        let keyword = Token::Function;
+        assert!(keyword.is_keyword());
+        assert!(!keyword.is_literal());
+        assert!(!keyword.is_operator());
+        assert!(!keyword.is_delimiter());
+
+        let literal = Token::Int(5);
+        assert!(literal.is_literal());
+        assert!(!literal.is_keyword());
+        assert!(!literal.is_operator());
+        assert!(!literal.is_delimiter());
+
+        assert!(Token::True.is_literal());
+        assert!(Token::True.is_keyword());
+
+        let operator = Token::Shl;
+        assert!(operator.is_operator());
+        assert!(!operator.is_keyword());
+        assert!(!operator.is_literal());
+        assert!(!operator.is_delimiter());
+
+        let delimiter = Token::Semicolon;
+        assert!(delimiter.is_delimiter());
+        assert!(!delimiter.is_keyword());
+        assert!(!delimiter.is_literal());
+        assert!(!delimiter.is_operator());
+
+        let ident = Token::Identifier(Box::from("x"));
+        assert!(!ident.is_keyword());
+        assert!(!ident.is_literal());
+        assert!(!ident.is_operator());
+        assert!(!ident.is_delimiter());
+    }
+
+    #[test]
+    fn test_for_each_token() {
+        let mut streamed = vec![];
+        Lexer::for_each_token(SAMPLE, |token| streamed.push(token));
+        assert_eq!(streamed, Lexer::tokens(SAMPLE));
+
+        let mut semicolons = 0;
+        Lexer::for_each_token(SAMPLE, |token| {
+            if token == Token::Semicolon {
+                semicolons += 1;
+            }
+        });
+        assert_eq!(semicolons, 11);
+    }
+
+    #[test]
+    fn test_lexer_fallible() {
+        let tokens = Lexer::new("let x = 5;")
+            .fallible()
+            .collect::<Result<Vec<Token>, LexError>>();
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                Token::Let,
+                Token::Identifier(Box::from("x")),
+                Token::Assign,
+                Token::Int(5),
+                Token::Semicolon,
+            ])
+        );
+
+        let tokens = Lexer::new("let x = @ # 5;")
+            .fallible()
+            .collect::<Result<Vec<Token>, LexError>>();
+        assert_eq!(
+            tokens,
+            Err(LexError::IllegalCharacter {
+                character: '@',
+                position: 8,
+                len: 1,
+            })
+        );
+
+        let run = Lexer::new("x @#$ y")
+            .fallible()
+            .filter_map(Result::err)
+            .collect::<Vec<LexError>>();
+        assert_eq!(run, vec![LexError::IllegalCharacter { character: '@', position: 2, len: 3 }]);
+        assert_eq!(run[0].to_string(), "illegal character '@' at 2..5");
+    }
+
+    #[test]
+    fn test_lexer_checkpoint() {
+        let mut lexer = Lexer::new("let five = 5; five + 1;");
+        assert_eq!(lexer.next(), Some(Token::Let));
+
+        let checkpoint = lexer.save();
+        let first = vec![lexer.next(), lexer.next(), lexer.next()];
+        assert_eq!(
+            first,
+            vec![
+                Some(Token::Identifier(Box::from("five"))),
+                Some(Token::Assign),
+                Some(Token::Int(5)),
+            ]
+        );
+
+        lexer.restore(checkpoint);
+        let second = vec![lexer.next(), lexer.next(), lexer.next()];
+        assert_eq!(first, second);
+        assert_eq!(lexer.next(), Some(Token::Semicolon));
+    }
+
+    #[test]
+    fn test_tokens_to_json() {
+        let tokens = Lexer::tokens("let x = 5;");
+        assert_eq!(
+            tokens_to_json(&tokens),
+            r#"["Let",{"Identifier":"x"},"Assign",{"Int":5},"Semicolon"]"#
+        );
+        assert_eq!(tokens_to_json(&[]), "[]");
+    }
+
+    #[test]
+    fn test_lexer_max_token_len() {
+        let input = "let abcdefghijklmnop = 1234567; let abc = 12;";
+        let expected = vec![
+            Token::Let,
+            Token::Illegal(Box::from("abcdefghijklmnop")),
+            Token::Assign,
+            Token::Illegal(Box::from("1234567;")),
+            Token::Let,
+            Token::Identifier(Box::from("abc")),
+            Token::Assign,
+            Token::Int(12),
+            Token::Semicolon,
+        ];
+
+        let lexer = Lexer::new(input).max_token_len(4);
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+
+        let tokens = Lexer::new(input)
+            .max_token_len(4)
+            .fallible()
+            .collect::<Result<Vec<Token>, LexError>>();
+        assert_eq!(tokens, Err(LexError::TokenTooLong { position: 4, len: 16 }));
+
+        assert_eq!(Lexer::tokens(input).len(), 10);
+
+        assert_eq!(
+            Lexer::new("12u8 1u8").max_token_len(3).collect::<Vec<Token>>(),
+            vec![
+                Token::Illegal(Box::from("12u8")),
+                Token::TypedInt { value: 1, suffix: IntType::U8 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_length_bounds() {
+        for keyword in keywords() {
+            assert!(
+                keyword.len() >= KEYWORD_MIN_LEN && keyword.len() <= KEYWORD_MAX_LEN,
+                "{} is outside the keyword length prefilter",
+                keyword
+            );
+        }
+    }
+
+    #[test]
+    fn test_line_tokens() {
+        let input = "let x = 5;
+
+x + 1; !x";
+        let expected = vec![
+            vec![
+                Token::Let,
+                Token::Identifier(Box::from("x")),
+                Token::Assign,
+                Token::Int(5),
+                Token::Semicolon,
+            ],
+            vec![],
+            vec![
+                Token::Identifier(Box::from("x")),
+                Token::Plus,
+                Token::Int(1),
+                Token::Semicolon,
+                Token::Bang,
+                Token::Identifier(Box::from("x")),
+            ],
+        ];
+
+        assert_eq!(Lexer::line_tokens(input), expected);
+
+        let mut lexer = Lexer::new(input);
+        lexer.by_ref().take(5).for_each(drop);
+        assert_eq!(lexer.line(), 1);
+        lexer.next();
+        assert_eq!(lexer.line(), 3);
+    }
+
+    #[test]
+    fn test_lexer_template_string() {
+        let input = "`hello world`;
+`hello ${name}!`
+`${ fn() { 1 } }`
+`never closed ${";
+        let expected = vec![
+            Token::TemplateString(Box::new([TemplatePart::Literal(String::from("hello world"))])),
+            Token::Semicolon,
+            Token::TemplateString(Box::new([
+                TemplatePart::Literal(String::from("hello ")),
+                TemplatePart::Expression {
+                    source: String::from("name"),
+                    span: 24..28,
+                },
+                TemplatePart::Literal(String::from("!")),
+            ])),
+            Token::TemplateString(Box::new([TemplatePart::Expression {
+                source: String::from(" fn() { 1 } "),
+                span: 35..47,
+            }])),
+            Token::Illegal(Box::from("`never closed ${")),
+        ];
+
+        let tokens = Lexer::tokens(input);
+        assert_eq!(tokens, expected);
+        // PartialEq skips the spans, the json has them
+        assert_eq!(tokens_to_json(&tokens), tokens_to_json(&expected));
+        assert_eq!(&input[24..28], "name");
+        assert_eq!(Lexer::tokens("``"), vec![Token::TemplateString(Box::new([]))]);
+        assert_eq!(
+            Lexer::tokens(r#"`${ "}" }`"#),
+            vec![Token::TemplateString(Box::new([TemplatePart::Expression {
+                source: String::from(r#" "}" "#),
+                span: 3..8,
+            }]))]
+        );
+    }
+
+    #[test]
+    fn test_token_accessors() {
+        assert_eq!(Token::Int(5).as_int(), Some(5));
+        assert_eq!(Token::Identifier(Box::from("five")).as_int(), None);
+        assert_eq!(Token::Semicolon.as_int(), None);
+
+        assert_eq!(Token::Identifier(Box::from("five")).as_identifier(), Some("five"));
+        assert_eq!(Token::Int(5).as_identifier(), None);
+        assert_eq!(Token::Let.as_identifier(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lexer_stats() {
+        let stats = Lexer::stats(SAMPLE);
+
+        assert_eq!(stats.get(&TokenKind::Semicolon), Some(&11));
+        assert_eq!(stats.get(&TokenKind::Let), Some(&4));
+        assert_eq!(stats.get(&TokenKind::Return), Some(&2));
+        assert_eq!(stats.get(&TokenKind::Identifier), Some(&11));
+        assert_eq!(stats.get(&TokenKind::Null), None);
+        assert_eq!(stats.values().sum::<usize>(), Lexer::tokens(SAMPLE).len());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_lexer_frequency() {
+        let frequency = Lexer::frequency(SAMPLE);
+
+        assert_eq!(
+            frequency[..4],
+            [
+                (TokenKind::Int, 12),
+                (TokenKind::Semicolon, 11),
+                (TokenKind::Identifier, 11),
+                (TokenKind::Let, 4),
+            ]
+        );
+        assert_eq!(frequency, Lexer::frequency(SAMPLE));
+    }
+
+    #[test]
+    fn test_token_size() {
+        assert!(std::mem::size_of::<Token>() <= 24);
+    }
+
+    #[test]
+    fn test_lexer_string() {
+        let input = r#""hello world" "a\"b\\c\n" "\u{1F600}" "\u{41}\u{10FFFF}"
+"\u{110000}" "\u{D800}" "\u{}" "\u{1234567}" "\q" "abc"#;
+        let expected = vec![
+            Token::String(Box::from("hello world")),
+            Token::String(Box::from("a\"b\\c\n")),
+            Token::String(Box::from("\u{1F600}")),
+            Token::String(Box::from("A\u{10FFFF}")),
+            Token::Illegal(Box::from(r#""\u{110000}""#)),
+            Token::Illegal(Box::from(r#""\u{D800}""#)),
+            Token::Illegal(Box::from(r#""\u{}""#)),
+            Token::Illegal(Box::from(r#""\u{1234567}""#)),
+            Token::Illegal(Box::from(r#""\q""#)),
+            Token::Illegal(Box::from(r#""abc"#)),
+        ];
+
+        let lexer = Lexer::new(input);
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+    }
+
+    #[test]
+    fn test_lexer_dots() {
+        let input = "fn(...args) { a.b; 1..5; 1..=5; .... }";
         let expected = vec![
-            Token::Let,
-            Token::Identifier(String::from("five")),
-            Token::Assign,
-            Token::Int(5),
-            Token::Semicolon,
-            Token::Let,
-            Token::Identifier(String::from("ten")),
-            Token::Assign,
-            Token::Int(10),
-            Token::Semicolon,
-            Token::Let,
-            Token::Identifier(String::from("add")),
-            Token::Assign,
             Token::Function,
             Token::Lparen,
-            Token::Identifier(String::from("x")),
-            Token::Comma,
-            Token::Identifier(String::from("y")),
+            Token::Spread,
+            Token::Identifier(Box::from("args")),
             Token::Rparen,
             Token::Lsquirlybrace,
-            Token::Identifier(String::from("x")),
-            Token::Plus,
-            Token::Identifier(String::from("y")),
+            Token::Identifier(Box::from("a")),
+            Token::Dot,
+            Token::Identifier(Box::from("b")),
             Token::Semicolon,
-            Token::Rsquirlybrace,
+            Token::Int(1),
+            Token::DotDot,
+            Token::Int(5),
             Token::Semicolon,
-            Token::Let,
-            Token::Identifier(String::from("result")),
-            Token::Assign,
-            Token::Identifier(String::from("add")),
-            Token::Lparen,
-            Token::Identifier(String::from("five")),
-            Token::Comma,
-            Token::Identifier(String::from("ten")),
-            Token::Rparen,
+            Token::Int(1),
+            Token::DotDotEq,
+            Token::Int(5),
             Token::Semicolon,
+            Token::Spread,
+            Token::Dot,
+            Token::Rsquirlybrace,
         ];
 
         let lexer = Lexer::new(input);
         assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
     }
 
+    #[cfg(feature = "std")]
     #[test]
-    fn test_lexer_3() {
-        let input = "let five = 5;
-let ten = 10;
-let add = fn(x, y) {
-    x + y;
-};
-let result = add(five, ten);
-!-/*5;
-5 < 10 > 5;
-if (5 < 10) {
-    return true;
-} else {
-    return false;
-}
-10 == 10;
-10 != 9;";
+    fn test_lex_error_anyhow() {
+        let err = Lexer::new("let x = @;")
+            .fallible()
+            .collect::<Result<Vec<Token>, LexError>>()
+            .map_err(anyhow::Error::from)
+            .expect_err("@ is illegal");
+
+        assert_eq!(err.to_string(), "illegal character '@' at 8..9");
+        assert_eq!(
+            LexError::TokenTooLong { position: 4, len: 16 }.to_string(),
+            "token of length 16 is too long at 4..20"
+        );
+    }
 
+    #[test]
+    fn test_lexer_case_insensitive_keywords() {
+        let input = "LET x = FN() { Return TRUE; }; LetItBe";
         let expected = vec![
             Token::Let,
-            Token::Identifier(String::from("five")),
-            Token::Assign,
-            Token::Int(5),
-            Token::Semicolon,
-            Token::Let,
-            Token::Identifier(String::from("ten")),
-            Token::Assign,
-            Token::Int(10),
-            Token::Semicolon,
-            Token::Let,
-            Token::Identifier(String::from("add")),
+            Token::Identifier(Box::from("x")),
             Token::Assign,
             Token::Function,
             Token::Lparen,
-            Token::Identifier(String::from("x")),
-            Token::Comma,
-            Token::Identifier(String::from("y")),
             Token::Rparen,
             Token::Lsquirlybrace,
-            Token::Identifier(String::from("x")),
-            Token::Plus,
-            Token::Identifier(String::from("y")),
+            Token::Return,
+            Token::True,
             Token::Semicolon,
             Token::Rsquirlybrace,
             Token::Semicolon,
+            Token::Identifier(Box::from("LetItBe")),
+        ];
+
+        let lexer = Lexer::new(input).case_insensitive_keywords(true);
+        assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+
+        assert_eq!(
+            Lexer::tokens("LET let"),
+            vec![Token::Identifier(Box::from("LET")), Token::Let]
+        );
+    }
+
+    #[test]
+    fn test_tokens_with_trivia() {
+        let input = "// the answer
+// to everything
+let x = 42; // trailing
+x / 2;
+// dangling";
+        let tokens = Lexer::tokens_with_trivia(input);
+
+        assert_eq!(
+            tokens[0],
+            (
+                vec![
+                    Comment {
+                        text: String::from(" the answer"),
+                        span: 0..13,
+                    },
+                    Comment {
+                        text: String::from(" to everything"),
+                        span: 14..30,
+                    },
+                ],
+                Token::Let,
+            )
+        );
+        assert_eq!(
+            tokens[5],
+            (
+                vec![Comment {
+                    text: String::from(" trailing"),
+                    span: 43..54,
+                }],
+                Token::Identifier(Box::from("x")),
+            )
+        );
+        assert!(tokens[1..5].iter().all(|(comments, _)| comments.is_empty()));
+        assert_eq!(tokens.len(), 9);
+
+        assert_eq!(
+            Lexer::tokens(input),
+            tokens.into_iter().map(|(_, token)| token).collect::<Vec<Token>>()
+        );
+    }
+
+    #[test]
+    fn test_stream_lexer() {
+        let input = format!(
+            "{}
+// a comment that \"spans\" a chunk
+let s = \"hello, world\"; `t ${{1 + 2}}` a...b << c..=d;
+let h = <<<END
+body with END in it
+END
+;",
+            SAMPLE
+        );
+        let expected = Lexer::tokens(&input);
+
+        for split in 0..=input.len() {
+            let mut stream = StreamLexer::new();
+            let mut tokens = stream.feed(&input[..split]);
+            tokens.extend(stream.feed(&input[split..]));
+            tokens.extend(stream.finish());
+            assert_eq!(tokens, expected, "split at {}", split);
+            assert_eq!(tokens_to_json(&tokens), tokens_to_json(&expected), "split at {}", split);
+        }
+
+        let mut stream = StreamLexer::new();
+        let mut tokens = vec![];
+        for c in input.chars() {
+            tokens.extend(stream.feed(&c.to_string()));
+        }
+        tokens.extend(stream.finish());
+        assert_eq!(tokens, expected);
+
+        let mut stream = StreamLexer::new();
+        assert_eq!(stream.feed("let abc"), vec![Token::Let]);
+        assert_eq!(
+            stream.feed("def = \"unterminated"),
+            vec![Token::Identifier(Box::from("abcdef")), Token::Assign]
+        );
+        assert_eq!(
+            stream.finish(),
+            vec![Token::Illegal(Box::from("\"unterminated"))]
+        );
+    }
+
+    #[test]
+    fn test_lexer_illegal_runs() {
+        assert_eq!(Lexer::tokens("@@@"), vec![Token::Illegal(Box::from("@@@"))]);
+        assert_eq!(
+            Lexer::tokens("@ @ @"),
+            vec![
+                Token::Illegal(Box::from("@")),
+                Token::Illegal(Box::from("@")),
+                Token::Illegal(Box::from("@")),
+            ]
+        );
+        assert_eq!(
+            Lexer::tokens("x@#$+1"),
+            vec![
+                Token::Identifier(Box::from("x")),
+                Token::Illegal(Box::from("@#$")),
+                Token::Plus,
+                Token::Int(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_char_pairs() {
+        let pairs = Lexer::char_pairs("!=<<").collect::<Vec<(char, Option<char>)>>();
+        assert_eq!(
+            pairs,
+            vec![('!', Some('=')), ('=', Some('<')), ('<', Some('<')), ('<', None)]
+        );
+        assert_eq!(Lexer::char_pairs("").next(), None);
+    }
+
+    #[test]
+    fn test_lexer_keep_comments() {
+        let input = "let x = 4 / 2; // half
+// done";
+        let expected = vec![
             Token::Let,
-            Token::Identifier(String::from("result")),
+            Token::Identifier(Box::from("x")),
             Token::Assign,
-            Token::Identifier(String::from("add")),
-            Token::Lparen,
-            Token::Identifier(String::from("five")),
-            Token::Comma,
-            Token::Identifier(String::from("ten")),
-            Token::Rparen,
-            Token::Semicolon,
-            Token::Bang,
-            Token::Minus,
+            Token::Int(4),
             Token::Slash,
-            Token::Asterisk,
-            Token::Int(5),
-            Token::Semicolon,
-            Token::Int(5),
-            Token::Lt,
-            Token::Int(10),
-            Token::Gt,
-            Token::Int(5),
-            Token::Semicolon,
-            Token::If,
-            Token::Lparen,
-            Token::Int(5),
-            Token::Lt,
-            Token::Int(10),
-            Token::Rparen,
-            Token::Lsquirlybrace,
-            Token::Return,
-            Token::True,
-            Token::Semicolon,
-            Token::Rsquirlybrace,
-            Token::Else,
-            Token::Lsquirlybrace,
-            Token::Return,
-            Token::False,
-            Token::Semicolon,
-            Token::Rsquirlybrace,
-            Token::Int(10),
-            Token::Equal,
-            Token::Int(10),
-            Token::Semicolon,
-            Token::Int(10),
-            Token::NotEqual,
-            Token::Int(9),
+            Token::Int(2),
             Token::Semicolon,
+            Token::Comment(Box::from(" half")),
+            Token::Comment(Box::from(" done")),
         ];
 
-        let lexer = Lexer::new(input);
+        let lexer = Lexer::new(input).keep_comments(true);
         assert_eq!(lexer.into_iter().collect::<Vec<Token>>(), expected);
+
+        let tokens = Lexer::new(input)
+            .keep_comments(true)
+            .fallible()
+            .collect::<Result<Vec<Token>, LexError>>();
+        assert_eq!(tokens, Ok(expected));
+        assert_eq!(Lexer::tokens(input).len(), 7);
+    }
+
+    #[test]
+    fn test_token_from_str() {
+        assert_eq!("==".parse::<Token>(), Ok(Token::Equal));
+        assert_eq!("<<".parse::<Token>(), Ok(Token::Shl));
+        assert_eq!("fn".parse::<Token>(), Ok(Token::Function));
+        assert_eq!(" null ".parse::<Token>(), Ok(Token::Null));
+        assert_eq!("foo".parse::<Token>(), Ok(Token::Identifier(Box::from("foo"))));
+        assert_eq!("42".parse::<Token>(), Ok(Token::Int(42)));
+        assert_eq!(r#""hi""#.parse::<Token>(), Ok(Token::String(Box::from("hi"))));
+
+        assert_eq!("".parse::<Token>(), Err(ParseTokenError::Empty));
+        assert_eq!("// nothing".parse::<Token>(), Err(ParseTokenError::Empty));
+        assert_eq!("= =".parse::<Token>(), Err(ParseTokenError::MultipleTokens));
+        assert_eq!("foo@".parse::<Token>(), Err(ParseTokenError::MultipleTokens));
+        assert_eq!("@".parse::<Token>(), Err(ParseTokenError::Illegal(Box::from("@"))));
+    }
+
+    fn assert_roundtrip(src: &str) {
+        let tokens = Lexer::tokens(src);
+        let joined = tokens
+            .iter()
+            .map(|token| token.to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+        assert_eq!(Lexer::tokens(&joined), tokens, "rendered as {:?}", joined);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        assert_roundtrip("=+(){},;");
+        assert_roundtrip(SAMPLE_ADD);
+        assert_roundtrip(SAMPLE);
+        assert_roundtrip(r#"~a & b | c ^ d << 1 >> 2; ...xs a.b 1..2 1..=2 null"#);
+        assert_roundtrip(r#""quote \" slash \\ tab \t \u{1F600} bell \u{7}" `t ${x + 1}!`"#);
+        assert_roundtrip("@#$");
+
+        let illegal = Lexer::tokens("@\u{0}");
+        assert_eq!(illegal, vec![Token::Illegal(Box::from("@\u{0}"))]);
+        assert_eq!(illegal[0].to_string(), "@\\x00");
+        assert_eq!(
+            Lexer::tokens(&illegal[0].to_string()),
+            vec![
+                Token::Illegal(Box::from("@")),
+                Token::Backslash,
+                Token::Identifier(Box::from("x")),
+                Token::Int(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typed_ints() {
+        assert_eq!(
+            Lexer::tokens("5u8 100i32 7usize 42 300u8 5u7 9x;"),
+            vec![
+                Token::TypedInt { value: 5, suffix: IntType::U8 },
+                Token::TypedInt { value: 100, suffix: IntType::I32 },
+                Token::TypedInt { value: 7, suffix: IntType::Usize },
+                Token::Int(42),
+                Token::Illegal(Box::from("300u8")),
+                Token::Illegal(Box::from("5u7")),
+                Token::Illegal(Box::from("9x")),
+                Token::Semicolon,
+            ]
+        );
+
+        let token = Token::TypedInt { value: 127, suffix: IntType::I8 };
+        assert_eq!(token.to_string(), "127i8");
+        assert_eq!(token.to_json(), r#"{"TypedInt":{"value":127,"suffix":"I8"}}"#);
+        assert_eq!(token.kind(), TokenKind::TypedInt);
+    }
+
+    #[test]
+    fn test_skip_shebang() {
+        let input = "#!/usr/bin/env monkey\nlet x = 5;";
+        let mut lexer = Lexer::new(input).skip_shebang(true);
+        assert_eq!(lexer.next(), Some(Token::Let));
+        assert_eq!(lexer.line(), 2);
+        assert_eq!(
+            lexer.collect::<Vec<Token>>(),
+            vec![
+                Token::Identifier(Box::from("x")),
+                Token::Assign,
+                Token::Int(5),
+                Token::Semicolon,
+            ]
+        );
+
+        assert_eq!(
+            Lexer::new("let x = 5;").skip_shebang(true).collect::<Vec<Token>>(),
+            Lexer::tokens("let x = 5;")
+        );
+        assert_eq!(Lexer::tokens(input)[..2], [Token::Illegal(Box::from("#")), Token::Bang]);
+        assert_eq!(
+            Lexer::new("x #!y").skip_shebang(true).collect::<Vec<Token>>(),
+            vec![
+                Token::Identifier(Box::from("x")),
+                Token::Illegal(Box::from("#")),
+                Token::Bang,
+                Token::Identifier(Box::from("y")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lambda_tokens() {
+        assert_eq!(
+            Lexer::tokens("(\\x -> x * 2)(5) - -1 ->"),
+            vec![
+                Token::Lparen,
+                Token::Backslash,
+                Token::Identifier(Box::from("x")),
+                Token::Arrow,
+                Token::Identifier(Box::from("x")),
+                Token::Asterisk,
+                Token::Int(2),
+                Token::Rparen,
+                Token::Lparen,
+                Token::Int(5),
+                Token::Rparen,
+                Token::Minus,
+                Token::Minus,
+                Token::Int(1),
+                Token::Arrow,
+            ]
+        );
+        assert_eq!(Lexer::tokens("@\\x")[0], Token::Illegal(Box::from("@")));
+    }
+
+    #[test]
+    #[should_panic(expected = "token 1 of \"let x\" differs: expected Identifier(\"y\")")]
+    fn test_assert_tokens_reports_index() {
+        assert_tokens("let x", &[Token::Let, Token::Identifier(Box::from("y"))]);
+    }
+
+    #[test]
+    fn test_fixity() {
+        assert_eq!(Token::Minus.fixity(), Some(Fixity::Both));
+        assert_eq!(Token::Bang.fixity(), Some(Fixity::Prefix));
+        assert_eq!(Token::Plus.fixity(), Some(Fixity::Infix));
+        assert_eq!(Token::Slash.fixity(), Some(Fixity::Infix));
+        assert_eq!(Token::Semicolon.fixity(), None);
+
+        let operators = "== != = + - ! * / < > ~ & | ^ << >> . ... .. ..= \\ ->";
+        for token in Lexer::tokens(operators).into_iter().chain(Lexer::tokens(SAMPLE)) {
+            assert_eq!(token.fixity().is_some(), token.is_operator(), "{:?}", token);
+        }
+    }
+
+    #[test]
+    fn test_with_text() {
+        let input = "let greeting = \"hi there\"; // done";
+        let pairs = Lexer::new(input).with_text().collect::<Vec<(Token, &str)>>();
+
+        assert_eq!(
+            pairs,
+            vec![
+                (Token::Let, "let"),
+                (Token::Identifier(Box::from("greeting")), "greeting"),
+                (Token::Assign, "="),
+                (Token::String(Box::from("hi there")), "\"hi there\""),
+                (Token::Semicolon, ";"),
+            ]
+        );
+
+        let pairs = Lexer::new(input).keep_comments(true).with_text().collect::<Vec<_>>();
+        assert_eq!(pairs.last(), Some(&(Token::Comment(Box::from(" done")), "// done")));
+    }
+
+    #[test]
+    fn test_longest_token() {
+        assert_eq!(
+            Lexer::longest_token("let a = thisIdentifierIsSuspiciouslyLong + 12345;"),
+            Some((Token::Identifier(Box::from("thisIdentifierIsSuspiciouslyLong")), 32))
+        );
+        assert_eq!(Lexer::longest_token("ab cd"), Some((Token::Identifier(Box::from("ab")), 2)));
+        assert_eq!(Lexer::longest_token("ab \"e\""), Some((Token::String(Box::from("e")), 3)));
+        assert_eq!(Lexer::longest_token(" // nothing"), None);
+    }
+
+    // expr := term ('+' term)*, term := atom ('*' atom)*, atom := int | '(' expr ')'
+    fn eval_expr(stream: &mut TokenStream) -> Result<usize, UnexpectedToken> {
+        let mut value = eval_term(stream)?;
+        while stream.peek() == Some(&Token::Plus) {
+            stream.next();
+            value += eval_term(stream)?;
+        }
+        return Ok(value);
+    }
+
+    fn eval_term(stream: &mut TokenStream) -> Result<usize, UnexpectedToken> {
+        let mut value = eval_atom(stream)?;
+        while stream.peek() == Some(&Token::Asterisk) {
+            stream.next();
+            value *= eval_atom(stream)?;
+        }
+        return Ok(value);
+    }
+
+    fn eval_atom(stream: &mut TokenStream) -> Result<usize, UnexpectedToken> {
+        if stream.expect(TokenKind::Lparen).is_ok() {
+            let value = eval_expr(stream)?;
+            stream.expect(TokenKind::Rparen)?;
+            return Ok(value);
+        }
+        let token = stream.expect(TokenKind::Int)?;
+        return Ok(token.as_int().expect("expected an Int"));
+    }
+
+    #[test]
+    fn test_token_stream() {
+        let mut stream = TokenStream::from("1 + 2 * (3 + 4)");
+        assert_eq!(eval_expr(&mut stream), Ok(15));
+        assert_eq!(stream.peek(), None);
+
+        let mut stream = TokenStream::from("(1 + 2");
+        assert_eq!(
+            eval_expr(&mut stream),
+            Err(UnexpectedToken { expected: TokenKind::Rparen, found: None, index: 4 })
+        );
+
+        let mut stream = TokenStream::from(vec![Token::Plus]);
+        let err = stream.expect(TokenKind::Int).unwrap_err();
+        assert_eq!(err.to_string(), "expected Int, found Plus at token 0");
+        assert_eq!(stream.cursor(), 0);
+        assert_eq!(stream.next(), Some(Token::Plus));
+        stream.set_cursor(0);
+        assert_eq!(stream.peek(), Some(&Token::Plus));
+    }
+
+    #[test]
+    fn test_token_hash() {
+        let set = Lexer::tokens("let x = x + `${x}`;")
+            .into_iter()
+            .collect::<std::collections::HashSet<Token>>();
+
+        assert_eq!(set.len(), 6);
+        assert!(set.contains(&Token::Identifier(Box::from("x"))));
+        assert!(!set.contains(&Token::Identifier(Box::from("y"))));
+        assert!(set.contains(&Token::TemplateString(Box::from([TemplatePart::Expression {
+            source: String::from("x"),
+            span: 0..0,
+        }]))));
+        assert_eq!(Lexer::tokens("`${x}`"), Lexer::tokens(" `${x}`"));
+    }
+
+    #[test]
+    fn test_no_keywords() {
+        assert_eq!(Lexer::tokens("fn"), vec![Token::Function]);
+        assert_eq!(
+            Lexer::new("fn if x").no_keywords(true).collect::<Vec<Token>>(),
+            vec![
+                Token::Identifier(Box::from("fn")),
+                Token::Identifier(Box::from("if")),
+                Token::Identifier(Box::from("x")),
+            ]
+        );
+        assert_eq!(
+            Lexer::new("FN").case_insensitive_keywords(true).no_keywords(true).next(),
+            Some(Token::Identifier(Box::from("FN")))
+        );
+    }
+
+    #[test]
+    fn test_brackets() {
+        assert_eq!(
+            Lexer::tokens("xs[0]"),
+            vec![Token::Identifier(Box::from("xs")), Token::Lbracket, Token::Int(0), Token::Rbracket]
+        );
+    }
+
+    #[test]
+    fn test_is_balanced() {
+        assert!(Lexer::is_balanced(""));
+        assert!(Lexer::is_balanced("let f = fn(x) { return [x, (x + 1)]; };"));
+        assert!(Lexer::is_balanced("\"(\" `{` // ]"));
+
+        assert!(!Lexer::is_balanced("fn(x) {"));
+        assert!(!Lexer::is_balanced("x)"));
+        assert!(!Lexer::is_balanced("([)]"));
+    }
+
+    #[test]
+    fn test_heredoc() {
+        let input = "let text = <<<END
+hello \\n ${world}
+  END
+END
+;";
+        let mut lexer = Lexer::new(input);
+        assert_eq!(
+            lexer.by_ref().take(4).collect::<Vec<Token>>(),
+            vec![
+                Token::Let,
+                Token::Identifier(Box::from("text")),
+                Token::Assign,
+                Token::String(Box::from("hello \\n ${world}\n  END")),
+            ]
+        );
+        assert_eq!(lexer.line(), 4);
+        assert_eq!(lexer.next(), Some(Token::Semicolon));
+        assert_eq!(lexer.line(), 5);
+
+        assert_eq!(
+            Lexer::tokens("<<<EOF\nthe EOF is not here\nEOF"),
+            vec![Token::String(Box::from("the EOF is not here"))]
+        );
+        assert_eq!(Lexer::tokens("<<<EOF\nEOF"), vec![Token::String(Box::from(""))]);
+        assert_eq!(
+            Lexer::tokens("<<<EOF\r\none\r\ntwo\r\nEOF\r\n"),
+            vec![Token::String(Box::from("one\r\ntwo"))]
+        );
+        assert_eq!(Lexer::tokens("<<<EOF\nnever closed")[0].kind(), TokenKind::Illegal);
+        assert_eq!(
+            Lexer::tokens("x <<< 1"),
+            vec![Token::Identifier(Box::from("x")), Token::Shl, Token::Lt, Token::Int(1)]
+        );
+    }
+
+    #[test]
+    fn test_indentation() {
+        let input = "let f = fn(x)
+    let y = x
+
+    if y
+        // nested
+        return y
+return f";
+        assert_eq!(
+            Lexer::new(input).indentation(true).map(|token| token.kind()).collect::<Vec<_>>(),
+            vec![
+                TokenKind::Let,
+                TokenKind::Identifier,
+                TokenKind::Assign,
+                TokenKind::Function,
+                TokenKind::Lparen,
+                TokenKind::Identifier,
+                TokenKind::Rparen,
+                TokenKind::Indent,
+                TokenKind::Let,
+                TokenKind::Identifier,
+                TokenKind::Assign,
+                TokenKind::Identifier,
+                TokenKind::If,
+                TokenKind::Identifier,
+                TokenKind::Indent,
+                TokenKind::Return,
+                TokenKind::Identifier,
+                TokenKind::Dedent,
+                TokenKind::Dedent,
+                TokenKind::Return,
+                TokenKind::Identifier,
+            ]
+        );
+        assert!(!Lexer::tokens(input).contains(&Token::Indent));
+
+        let tokens = Lexer::new("a\n  b\n    c").indentation(true).collect::<Vec<Token>>();
+        assert_eq!(tokens[tokens.len() - 2..], [Token::Dedent, Token::Dedent]);
+
+        let mut lexer = Lexer::new("a\n  b\n  c").indentation(true);
+        lexer.next();
+        let checkpoint = lexer.save();
+        let first = lexer.by_ref().take(3).collect::<Vec<Token>>();
+        lexer.restore(checkpoint);
+        assert_eq!(lexer.by_ref().take(3).collect::<Vec<Token>>(), first);
+        assert_eq!(first[0], Token::Indent);
+
+        assert_eq!(
+            Lexer::new("x = \"a\nb\" c").indentation(true).collect::<Vec<Token>>(),
+            vec![
+                Token::Identifier(Box::from("x")),
+                Token::Assign,
+                Token::String(Box::from("a\nb")),
+                Token::Identifier(Box::from("c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indentation_errors() {
+        let mixed = Lexer::new("a\n  b\n\tc").indentation(true).collect::<Vec<Token>>();
+        assert!(mixed.contains(&Token::Illegal(Box::from("\t"))), "{:?}", mixed);
+
+        let uneven = Lexer::new("a\n    b\n  c").indentation(true).collect::<Vec<Token>>();
+        assert_eq!(
+            uneven,
+            vec![
+                Token::Identifier(Box::from("a")),
+                Token::Indent,
+                Token::Identifier(Box::from("b")),
+                Token::Illegal(Box::from("  ")),
+                Token::Dedent,
+                Token::Identifier(Box::from("c")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_const_keyword() {
+        assert_eq!(
+            Lexer::tokens("const x = 5; constant"),
+            vec![
+                Token::Const,
+                Token::Identifier(Box::from("x")),
+                Token::Assign,
+                Token::Int(5),
+                Token::Semicolon,
+                Token::Identifier(Box::from("constant")),
+            ]
+        );
+        assert!(Token::Const.is_keyword());
+        assert!(keywords().contains(&"const"));
+    }
+
+    #[test]
+    fn test_lint_indentation() {
+        let input = "fn() {\n    clean;\n\t  mixed;\n\t\ttabs;\n}";
+        let spans = Lexer::lint_indentation(input);
+
+        assert_eq!(spans, vec![18..21]);
+        assert_eq!(&input[spans[0].clone()], "\t  ");
+        assert_eq!(Lexer::lint_indentation(""), vec![]);
+    }
+
+    #[test]
+    fn test_keep_int_text() {
+        let tokens = Lexer::new("007 + 1_000").keep_int_text(true).collect::<Vec<Token>>();
+        assert_eq!(
+            tokens,
+            vec![Token::RawInt(Box::from("007")), Token::Plus, Token::RawInt(Box::from("1_000"))]
+        );
+        assert_eq!(tokens[0].as_int(), Some(7));
+        assert_eq!(tokens[2].as_int(), Some(1000));
+        assert_eq!(tokens[0].to_string(), "007");
+        assert_eq!(tokens[0].to_json(), r#"{"RawInt":"007"}"#);
+
+        assert_eq!(Lexer::tokens("007"), vec![Token::Int(7)]);
+        assert_eq!(
+            Lexer::new("1_000u16").keep_int_text(true).collect::<Vec<Token>>(),
+            vec![Token::TypedInt { value: 1000, suffix: IntType::U16 }]
+        );
+
+        assert_eq!(
+            Lexer::new("18446744073709551616").keep_int_text(true).collect::<Vec<Token>>(),
+            vec![Token::Illegal(Box::from("18446744073709551616"))]
+        );
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let raw = |input| {
+            return Lexer::new(input).keep_int_text(true).collect::<Vec<Token>>();
+        };
+        let int = |text: &str| Token::RawInt(Box::from(text));
+
+        assert_eq!(raw("1_2_3"), vec![int("1_2_3")]);
+        assert_eq!(raw("5_"), vec![int("5"), Token::Illegal(Box::from("_"))]);
+        assert_eq!(raw("1__0"), vec![int("1"), Token::Illegal(Box::from("__")), int("0")]);
+        assert_eq!(
+            raw("1_u8"),
+            vec![
+                int("1"),
+                Token::Illegal(Box::from("_")),
+                Token::Identifier(Box::from("u")),
+                int("8"),
+            ]
+        );
+
+        // without keep_int_text `_` is never part of an int
+        assert_eq!(
+            Lexer::tokens("1_000"),
+            vec![Token::Int(1), Token::Illegal(Box::from("_")), Token::Int(0)]
+        );
+    }
+
+    #[test]
+    fn test_oversized_int() {
+        assert_eq!(
+            Lexer::tokens("18446744073709551616;"),
+            vec![Token::Illegal(Box::from("18446744073709551616")), Token::Semicolon]
+        );
+        assert_eq!(Lexer::tokens("18446744073709551615"), vec![Token::Int(usize::MAX)]);
+    }
+
+    #[test]
+    fn test_illegal_display_escapes_control_chars() {
+        let tokens = Lexer::tokens("\0 @\x07# x");
+        assert_eq!(tokens[0], Token::Illegal(Box::from("\0")));
+        assert_eq!(tokens[0].to_string(), "\\x00");
+        assert_eq!(tokens[1].to_string(), "@\\x07#");
+        assert_eq!(Token::Illegal(Box::from("é@")).to_string(), "é@");
+    }
+
+    #[test]
+    fn test_highlight_group() {
+        let groups = Lexer::new("let x = 5; \"s\" `t` true // c\n@")
+            .keep_comments(true)
+            .map(|token| token.highlight_group())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(
+            groups,
+            vec![
+                "keyword",
+                "identifier",
+                "operator",
+                "number",
+                "punctuation",
+                "string",
+                "string",
+                "keyword",
+                "comment",
+                "error",
+            ]
+        );
+        assert_eq!(Token::Indent.highlight_group(), "whitespace");
+    }
+
+    #[test]
+    fn test_peek_n() {
+        let mut lexer = Lexer::new("x = 5;");
+        assert_eq!(lexer.peek_n(1), Some(&Token::Assign));
+        assert_eq!(lexer.peek_n(0), Some(&Token::Identifier(Box::from("x"))));
+        assert_eq!(lexer.peek_n(4), None);
+
+        assert_eq!(lexer.next(), Some(Token::Identifier(Box::from("x"))));
+        assert_eq!(lexer.next(), Some(Token::Assign));
+        assert_eq!(lexer.peek_n(0), Some(&Token::Int(5)));
+        assert_eq!(lexer.collect::<Vec<Token>>(), vec![Token::Int(5), Token::Semicolon]);
+
+        let mut lexer = Lexer::new("a\nb\nc");
+        lexer.peek_n(2);
+        assert_eq!(lexer.line(), 1);
+        lexer.next();
+        lexer.next();
+        assert_eq!(lexer.line(), 2);
+    }
+
+    #[test]
+    fn test_peek_n_checkpoint() {
+        let mut lexer = Lexer::new("let x = 5;");
+        lexer.next();
+        lexer.peek_n(2);
+        let checkpoint = lexer.save();
+
+        assert_eq!(lexer.next(), Some(Token::Identifier(Box::from("x"))));
+        lexer.restore(checkpoint);
+        assert_eq!(lexer.collect::<Vec<Token>>(), Lexer::tokens("x = 5;"));
+    }
+
+    #[test]
+    fn test_peek_n_adapters() {
+        let mut lexer = Lexer::new("let x = 5;");
+        lexer.peek_n(2);
+        assert_eq!(
+            lexer.with_text().collect::<Vec<(Token, &str)>>(),
+            vec![
+                (Token::Let, "let"),
+                (Token::Identifier(Box::from("x")), "x"),
+                (Token::Assign, "="),
+                (Token::Int(5), "5"),
+                (Token::Semicolon, ";"),
+            ]
+        );
+
+        let mut lexer = Lexer::new("x @ y");
+        lexer.peek_n(1);
+        assert_eq!(
+            lexer.fallible().collect::<Vec<Result<Token, LexError>>>(),
+            vec![
+                Ok(Token::Identifier(Box::from("x"))),
+                Err(LexError::IllegalCharacter { character: '@', position: 2, len: 1 }),
+                Ok(Token::Identifier(Box::from("y"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fallible_illegal_reasons() {
+        let errors = |lexer: Lexer| {
+            return lexer.fallible().filter_map(Result::err).collect::<Vec<LexError>>();
+        };
+
+        assert_eq!(
+            errors(Lexer::new("5u7 9x 300u8 99999999999999999999999 @")),
+            vec![
+                LexError::InvalidIntSuffix { position: 0, len: 3 },
+                LexError::InvalidIntSuffix { position: 4, len: 2 },
+                LexError::IntOutOfRange { position: 7, len: 5 },
+                LexError::IntOutOfRange { position: 13, len: 23 },
+                LexError::IllegalCharacter { character: '@', position: 37, len: 1 },
+            ]
+        );
+        assert_eq!(
+            errors(Lexer::new("abcdef 12345").max_token_len(4)),
+            vec![
+                LexError::TokenTooLong { position: 0, len: 6 },
+                LexError::TokenTooLong { position: 7, len: 5 },
+            ]
+        );
+        assert_eq!(
+            errors(Lexer::new("a\n    b\n  c").indentation(true)),
+            vec![LexError::BadIndentation { position: 8, len: 2 }]
+        );
+        assert_eq!(
+            LexError::InvalidIntSuffix { position: 0, len: 3 }.to_string(),
+            "invalid integer suffix at 0..3"
+        );
+
+        assert_eq!(
+            errors(Lexer::new(r#""\u{D800}" "\u{110000}" "abc"#)),
+            vec![
+                LexError::InvalidEscape { position: 0, len: 10 },
+                LexError::InvalidEscape { position: 11, len: 12 },
+                LexError::UnterminatedString { position: 24, len: 4 },
+            ]
+        );
+        assert_eq!(
+            LexError::UnterminatedString { position: 24, len: 4 }.to_string(),
+            "unterminated string at 24..28"
+        );
+        assert_eq!(
+            errors(Lexer::new("`${")),
+            vec![LexError::UnterminatedTemplate { position: 0, len: 3 }]
+        );
+        assert_eq!(
+            errors(Lexer::new("<<<END\nx")),
+            vec![LexError::UnterminatedHeredoc { position: 0, len: 8 }]
+        );
     }
 }
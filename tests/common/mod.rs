@@ -0,0 +1,67 @@
+// shared by the lexer benches and the integration tests, which each use only
+// part of it. benches pull it in with #[path]
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+const SEED: u64 = 0x5eed;
+
+const STATEMENTS: &[&str] = &[
+    "let five = 5;",
+    "let add = fn(x, y) { x + y; };",
+    "let result = add(five, 10); // a comment",
+    "if (5 < 10) { return true; } else { return false; }",
+    "!-/*5;",
+    "10 == 10; 10 != 9;",
+    "let greeting = \"hello world\";",
+    "`template ${five}` != null;",
+];
+
+// xorshift so the generated inputs are the same on every run
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    pub fn new() -> Generator {
+        return Generator { state: SEED };
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        return items[(self.state % items.len() as u64) as usize];
+    }
+
+    pub fn program(&mut self, statements: usize) -> String {
+        return (0..statements)
+            .map(|_| self.pick(STATEMENTS))
+            .collect::<Vec<&str>>()
+            .join("\n");
+    }
+}
+
+// a writer whose clones share one buffer, so output handed to
+// Repl::with_writer can still be read back afterwards
+#[derive(Clone, Default)]
+pub struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Captured {
+    pub fn text(&self) -> String {
+        return String::from_utf8_lossy(&self.0.borrow()).into_owned();
+    }
+}
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        return Ok(buf.len());
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
@@ -0,0 +1,43 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use interpreterbook::repl::Repl;
+use pretty_assertions::assert_eq;
+
+mod common;
+
+use common::Captured;
+
+fn run(input: &str) -> Result<String> {
+    let captured = Captured::default();
+    let mut repl = Repl::with_writer(captured.clone());
+
+    for line in input.lines() {
+        repl.run_line(line)?;
+    }
+
+    return Ok(captured.text());
+}
+
+#[test]
+fn test_golden_fixtures() -> Result<()> {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths = fs::read_dir(fixtures)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "monkey"));
+    paths.sort();
+
+    assert!(!paths.is_empty(), "no .monkey fixtures found");
+
+    for path in paths {
+        let input = fs::read_to_string(&path)?;
+        let expected = fs::read_to_string(path.with_extension("expected"))
+            .map_err(|e| anyhow::anyhow!("{}: missing .expected file: {}", path.display(), e))?;
+
+        let output = run(&input)?;
+        assert_eq!(output, expected, "golden mismatch for {}", path.display());
+    }
+
+    return Ok(());
+}
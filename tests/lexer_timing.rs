@@ -0,0 +1,29 @@
+use std::time::{Duration, Instant};
+
+use interpreterbook::token::Lexer;
+
+mod common;
+
+use common::Generator;
+
+// generous on purpose, this only has to catch accidental quadratic behaviour
+// (re-scanning the input per token), not small slowdowns
+const LIMIT: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_lexing_is_not_quadratic() {
+    let input = Generator::new().program(50_000);
+
+    let start = Instant::now();
+    let tokens = Lexer::tokens(&input);
+    let elapsed = start.elapsed();
+
+    assert!(tokens.len() > 300_000);
+    assert!(
+        elapsed < LIMIT,
+        "lexing {} bytes took {:?}, over the {:?} limit",
+        input.len(),
+        elapsed,
+        LIMIT
+    );
+}
@@ -0,0 +1,41 @@
+use std::{env, path::Path, process::Command};
+
+// the rest of the test run builds examples/no_std with the default std
+// feature on, which proves nothing about no_std. build it again without it,
+// in its own target dir so it doesn't wait on the lock cargo test holds
+fn build(target: Option<&str>) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var("CARGO").unwrap_or_else(|_| String::from("cargo"));
+
+    let mut command = Command::new(cargo);
+    command
+        .current_dir(root)
+        .env("CARGO_TARGET_DIR", root.join("target/no_std"))
+        .args(["build", "--quiet", "--example", "no_std", "--no-default-features"]);
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+
+    let output = command.output().expect("cargo runs");
+    assert!(
+        output.status.success(),
+        "no_std build for {:?} failed:\n{}",
+        target,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_no_std_example_builds() {
+    build(None);
+}
+
+// on the host std is still there to link, so only a target without std
+// catches a dependency that needs it. opt in with NO_STD_TARGET set to an
+// installed one, e.g. thumbv7em-none-eabihf
+#[test]
+fn test_no_std_example_builds_for_target() {
+    if let Ok(target) = env::var("NO_STD_TARGET") {
+        build(Some(&target));
+    }
+}
@@ -0,0 +1,56 @@
+use std::panic;
+
+use interpreterbook::token::{Lexer, StreamLexer, Token};
+
+// inputs that have panicked the lexer before. add new finds to the end
+const CORPUS: &[&str] = &[
+    // bigger than usize, the int parse used to expect() success
+    "99999999999999999999999999",
+    "let x = 18446744073709551616;",
+    "18446744073709551616u64",
+    "1_8446_7440_7370_9551_6160",
+    // unterminated strings and templates
+    "\"",
+    "\"abc",
+    "\"abc\\",
+    "\"\\u{",
+    "\"\\u{110000}\"",
+    "`",
+    "`${",
+    "`${ `${x",
+    "<<<END",
+    "<<<END\nno terminator",
+    // multibyte chars next to the things that slice the source
+    "é",
+    "\"é",
+    "//é",
+    "1é",
+    "\n\t é",
+];
+
+fn lex_no_panic(input: &str) {
+    let result = panic::catch_unwind(|| {
+        Lexer::tokens(input);
+        Lexer::new(input).fallible().for_each(drop);
+        Lexer::new(input).with_text().for_each(drop);
+        Lexer::new(input).keep_comments(true).keep_int_text(true).for_each(drop);
+        Lexer::new(input).indentation(true).for_each(drop);
+
+        let mut stream = StreamLexer::new();
+        for c in input.chars() {
+            stream.feed(&c.to_string());
+        }
+        stream.finish();
+
+        return input.parse::<Token>().ok();
+    });
+
+    assert!(result.is_ok(), "lexing {:?} panicked", input);
+}
+
+#[test]
+fn test_corpus_lexes_without_panicking() {
+    for input in CORPUS {
+        lex_no_panic(input);
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use pretty_assertions::assert_eq;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn run_repl(args: &[&str], input: &str) -> Result<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_repl"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())?;
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill()?;
+            bail!("repl did not exit within {:?}", TIMEOUT);
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert!(status.success());
+
+    let mut output = String::new();
+    child
+        .stdout
+        .take()
+        .expect("stdout is piped")
+        .read_to_string(&mut output)?;
+
+    return Ok(output);
+}
+
+#[test]
+fn test_dump_tokens() -> Result<()> {
+    let input = "let add = fn(x, y) {
+    x + y;
+};
+add(1, 2) != 3;";
+
+    let output = run_repl(&["--dump-tokens"], input)?;
+    assert_eq!(
+        output,
+        concat!(
+            r#"["Let",{"Identifier":"add"},"Assign","Function","Lparen",{"Identifier":"x"},"Comma","#,
+            r#"{"Identifier":"y"},"Rparen","Lsquirlybrace",{"Identifier":"x"},"Plus",{"Identifier":"y"},"#,
+            r#""Semicolon","Rsquirlybrace","Semicolon",{"Identifier":"add"},"Lparen",{"Int":1},"Comma","#,
+            r#"{"Int":2},"Rparen","NotEqual",{"Int":3},"Semicolon"]"#,
+            "\n"
+        )
+    );
+
+    return Ok(());
+}
+
+#[test]
+fn test_exits_on_eof() -> Result<()> {
+    let output = run_repl(&[], "let x = 5;\n!x\n")?;
+    assert_eq!(
+        output,
+        ">> Let
+Identifier(\"x\")
+Assign
+Int(5)
+Semicolon
+>> Bang
+Identifier(\"x\")
+>> "
+    );
+
+    return Ok(());
+}
+
+#[test]
+fn test_exit_stops_reading() -> Result<()> {
+    let output = run_repl(&[], "x\nexit\ny\n")?;
+    assert_eq!(output, ">> Identifier(\"x\")\n>> ");
+
+    return Ok(());
+}